@@ -0,0 +1,409 @@
+use core::Instruction;
+
+use crate::program::{Position, Program};
+use crate::AssembleError;
+
+#[derive(Debug, Clone, Copy)]
+struct Operand<'a> {
+    text: &'a str,
+    position: Position,
+}
+
+pub(crate) fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let mut program = Program::default();
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split(';').next().unwrap_or_default();
+        statement(&mut program, code, index + 1)?;
+    }
+    program.finish()
+}
+
+/// Splits off the first whitespace separated word of `text`, which starts at
+/// `column`.
+fn word(text: &str, line: usize, column: usize) -> Option<(Operand<'_>, &str, usize)> {
+    let start = text.len() - text.trim_start().len();
+    let rest = &text[start..];
+    if rest.is_empty() {
+        return None;
+    }
+    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let operand = Operand {
+        text: &rest[..end],
+        position: Position {
+            line,
+            column: column + start,
+        },
+    };
+    Some((operand, &rest[end..], column + start + end))
+}
+
+/// Splits a comma separated operand list.
+fn operands(text: &str, line: usize, column: usize) -> Vec<Operand<'_>> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut operands = Vec::new();
+    let mut offset = 0;
+    for part in text.split(',') {
+        let start = part.len() - part.trim_start().len();
+        operands.push(Operand {
+            text: part.trim(),
+            position: Position {
+                line,
+                column: column + offset + start,
+            },
+        });
+        offset += part.len() + 1;
+    }
+    operands
+}
+
+fn statement(program: &mut Program, code: &str, line: usize) -> Result<(), AssembleError> {
+    let Some((mut first, mut rest, mut column)) = word(code, line, 1) else {
+        return Ok(());
+    };
+
+    if let Some(name) = first.text.strip_suffix(':') {
+        if !name.starts_with(':') {
+            program.define_label(name, first.position)?;
+            match word(rest, line, column) {
+                Some(next) => (first, rest, column) = next,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    match first.text {
+        ":const" => {
+            let (name, rest, column) = word(rest, line, column)
+                .ok_or_else(|| first.position.error("`:const` expects a name and a value"))?;
+            let (value, ..) = word(rest, line, column)
+                .ok_or_else(|| name.position.error("`:const` expects a name and a value"))?;
+            let value = program.known(value.text, value.position)?;
+            return program.define_constant(name.text, value, name.position);
+        }
+        ":alias" => {
+            let (name, rest, column) = word(rest, line, column).ok_or_else(|| {
+                first
+                    .position
+                    .error("`:alias` expects a name and a register")
+            })?;
+            let (register, ..) = word(rest, line, column).ok_or_else(|| {
+                name.position
+                    .error("`:alias` expects a name and a register")
+            })?;
+            let register = program.register(register.text, register.position)?;
+            return program.define_alias(name.text, register, name.position);
+        }
+        _ => {}
+    }
+
+    let operands = operands(rest, line, column);
+    instruction(program, first, &operands)
+}
+
+fn instruction(
+    program: &mut Program,
+    mnemonic: Operand,
+    operands: &[Operand],
+) -> Result<(), AssembleError> {
+    let register = |operand: &Operand| program.register(operand.text, operand.position);
+    let expect = |count: usize| {
+        if operands.len() == count {
+            Ok(())
+        } else {
+            Err(mnemonic.position.error(format!(
+                "`{}` expects {count} operand{}",
+                mnemonic.text,
+                if count == 1 { "" } else { "s" }
+            )))
+        }
+    };
+
+    let upper = mnemonic.text.to_ascii_uppercase();
+    let instruction = match upper.as_str() {
+        ":BYTE" | "DB" => {
+            for operand in operands {
+                let byte = program.byte(operand.text, operand.position)?;
+                program.emit_byte(byte);
+            }
+            return Ok(());
+        }
+        "CLS" | "RET" | "SCR" | "SCL" | "EXIT" | "LOW" | "HIGH" | "AUDIO" => {
+            expect(0)?;
+            match upper.as_str() {
+                "CLS" => Instruction::Cls,
+                "RET" => Instruction::Ret,
+                "SCR" => Instruction::ScrollRight,
+                "SCL" => Instruction::ScrollLeft,
+                "EXIT" => Instruction::Exit,
+                "LOW" => Instruction::Lores,
+                "AUDIO" => Instruction::Audio,
+                _ => Instruction::Hires,
+            }
+        }
+        "SCD" | "SCU" | "PLANE" => {
+            expect(1)?;
+            let n = program.nibble(operands[0].text, operands[0].position)?;
+            match upper.as_str() {
+                "SCD" => Instruction::ScrollDown(n),
+                "SCU" => Instruction::ScrollUp(n),
+                _ => Instruction::Plane(n),
+            }
+        }
+        "SYS" | "CALL" => {
+            expect(1)?;
+            let target: fn(u16) -> Instruction = if upper == "SYS" {
+                Instruction::Sys
+            } else {
+                Instruction::Call
+            };
+            return program.emit_addressed(target, operands[0].text, operands[0].position);
+        }
+        "JP" => {
+            if operands.len() == 2 && register(&operands[0])? == 0 {
+                return program.emit_addressed(
+                    Instruction::JpV0,
+                    operands[1].text,
+                    operands[1].position,
+                );
+            }
+            expect(1)?;
+            return program.emit_addressed(Instruction::Jp, operands[0].text, operands[0].position);
+        }
+        "SE" | "SNE" => {
+            expect(2)?;
+            let x = register(&operands[0])?;
+            let equal = upper == "SE";
+            if program.is_register(operands[1].text) {
+                let y = register(&operands[1])?;
+                if equal {
+                    Instruction::SeReg { x, y }
+                } else {
+                    Instruction::SneReg { x, y }
+                }
+            } else {
+                let byte = program.byte(operands[1].text, operands[1].position)?;
+                if equal {
+                    Instruction::SeByte { x, byte }
+                } else {
+                    Instruction::SneByte { x, byte }
+                }
+            }
+        }
+        "ADD" => {
+            expect(2)?;
+            if operands[0].text.eq_ignore_ascii_case("I") {
+                Instruction::AddI(register(&operands[1])?)
+            } else {
+                let x = register(&operands[0])?;
+                if program.is_register(operands[1].text) {
+                    Instruction::AddReg {
+                        x,
+                        y: register(&operands[1])?,
+                    }
+                } else {
+                    let byte = program.byte(operands[1].text, operands[1].position)?;
+                    Instruction::AddByte { x, byte }
+                }
+            }
+        }
+        "OR" | "AND" | "XOR" | "SUB" | "SUBN" => {
+            expect(2)?;
+            let x = register(&operands[0])?;
+            let y = register(&operands[1])?;
+            match upper.as_str() {
+                "OR" => Instruction::Or { x, y },
+                "AND" => Instruction::And { x, y },
+                "XOR" => Instruction::Xor { x, y },
+                "SUB" => Instruction::Sub { x, y },
+                _ => Instruction::Subn { x, y },
+            }
+        }
+        "SHR" | "SHL" => {
+            // the source register is optional and defaults to Vx
+            if operands.len() != 1 {
+                expect(2)?;
+            }
+            let x = register(&operands[0])?;
+            let y = match operands.get(1) {
+                Some(operand) => register(operand)?,
+                None => x,
+            };
+            if upper == "SHR" {
+                Instruction::Shr { x, y }
+            } else {
+                Instruction::Shl { x, y }
+            }
+        }
+        "RND" => {
+            expect(2)?;
+            let x = register(&operands[0])?;
+            let byte = program.byte(operands[1].text, operands[1].position)?;
+            Instruction::Rnd { x, byte }
+        }
+        "DRW" => {
+            expect(3)?;
+            let x = register(&operands[0])?;
+            let y = register(&operands[1])?;
+            let n = program.nibble(operands[2].text, operands[2].position)?;
+            Instruction::Drw { x, y, n }
+        }
+        "SKP" | "SKNP" => {
+            expect(1)?;
+            let x = register(&operands[0])?;
+            if upper == "SKP" {
+                Instruction::Skp(x)
+            } else {
+                Instruction::Sknp(x)
+            }
+        }
+        "LD" => {
+            expect(2)?;
+            return load(program, operands[0], operands[1]);
+        }
+        _ => {
+            return Err(mnemonic
+                .position
+                .error(format!("unknown mnemonic `{}`", mnemonic.text)))
+        }
+    };
+    program.emit(instruction);
+    Ok(())
+}
+
+/// The many forms of `LD`.
+fn load(program: &mut Program, target: Operand, source: Operand) -> Result<(), AssembleError> {
+    let register = |operand: Operand| program.register(operand.text, operand.position);
+    let target_upper = target.text.to_ascii_uppercase();
+    let source_upper = source.text.to_ascii_uppercase();
+
+    let instruction = match (target_upper.as_str(), source_upper.as_str()) {
+        ("I", long) if long.starts_with("LONG ") => {
+            let address = source.text[4..].trim_start();
+            let column = source.position.column + source.text.len() - address.len();
+            let position = Position {
+                line: source.position.line,
+                column,
+            };
+            return program.emit_long(address, position);
+        }
+        ("I", _) => {
+            return program.emit_addressed(Instruction::LdI, source.text, source.position);
+        }
+        ("DT", _) => Instruction::LdDtVx(register(source)?),
+        ("ST", _) => Instruction::LdStVx(register(source)?),
+        ("PITCH", _) => Instruction::Pitch(register(source)?),
+        ("F", _) => Instruction::LdF(register(source)?),
+        ("HF", _) => Instruction::LdHf(register(source)?),
+        ("B", _) => Instruction::LdB(register(source)?),
+        ("R", _) => Instruction::SaveFlags(register(source)?),
+        ("[I]", _) => match register_range(program, source)? {
+            Some((x, y)) => Instruction::SaveRange { x, y },
+            None => Instruction::LdMem(register(source)?),
+        },
+        (_, "[I]") => match register_range(program, target)? {
+            Some((x, y)) => Instruction::LoadRange { x, y },
+            None => Instruction::LdRegs(register(target)?),
+        },
+        (_, "DT") => Instruction::LdVxDt(register(target)?),
+        (_, "K") => Instruction::LdVxK(register(target)?),
+        (_, "R") => Instruction::LoadFlags(register(target)?),
+        _ => {
+            let x = register(target)?;
+            if program.is_register(source.text) {
+                Instruction::LdReg {
+                    x,
+                    y: register(source)?,
+                }
+            } else {
+                let byte = program.byte(source.text, source.position)?;
+                Instruction::LdByte { x, byte }
+            }
+        }
+    };
+    program.emit(instruction);
+    Ok(())
+}
+
+/// `Vx - Vy`, or `None` for a single register.
+fn register_range(program: &Program, operand: Operand) -> Result<Option<(u8, u8)>, AssembleError> {
+    let Some((first, last)) = operand.text.split_once('-') else {
+        return Ok(None);
+    };
+    let x = program.register(first.trim(), operand.position)?;
+    let y = program.register(last.trim(), operand.position)?;
+    Ok(Some((x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statements() {
+        let rom = assemble(
+            "
+            start:  CLS             ; clear the screen
+                    LD V0, 5
+                    ADD V1, V0
+                    LD F, V0
+                    DRW V1, V2, 5
+                    SE V0, 0x05
+                    RND V3, 0xFF
+                    SHR V4
+                    JP start
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            rom,
+            [
+                0x00, 0xE0, 0x60, 0x05, 0x81, 0x04, 0xF0, 0x29, 0xD1, 0x25, 0x30, 0x05, 0xC3, 0xFF,
+                0x84, 0x46, 0x12, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels_constants_and_aliases() {
+        let rom = assemble(
+            "
+            :const SPEED 3
+            :alias X V4
+                    LD X, SPEED
+                    LD I, sprite
+                    CALL draw
+                    LD I, LONG sprite
+                    LD [I], V1 - V3
+            draw:   RET
+            sprite: :byte 0xF0, 0x90
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            rom,
+            [
+                0x64, 0x03, 0xA2, 0x0E, 0x22, 0x0C, 0xF0, 0x00, 0x02, 0x0E, 0x51, 0x32, 0x00, 0xEE,
+                0xF0, 0x90
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = assemble("LD V0, 1\n  JP nowhere").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 6));
+        assert_eq!(error.message, "`nowhere` is not defined");
+
+        let error = assemble("  DRW V0, V1").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 3));
+        assert_eq!(error.message, "`DRW` expects 3 operands");
+
+        let error = assemble("LD V0, VG").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 8));
+
+        let error = assemble("MOV V0, V1").unwrap_err();
+        assert_eq!(error.message, "unknown mnemonic `MOV`");
+    }
+}
@@ -0,0 +1,106 @@
+mod cowgod;
+mod error;
+mod octo;
+mod program;
+
+pub use error::AssembleError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// Octo, e.g. `v1 += 2`.
+    Octo,
+    /// Cowgod's mnemonics, e.g. `ADD V1, 2`.
+    Cowgod,
+}
+
+impl Syntax {
+    pub fn from_name(name: &str) -> Option<Syntax> {
+        match name {
+            "octo" => Some(Syntax::Octo),
+            "cowgod" => Some(Syntax::Cowgod),
+            _ => None,
+        }
+    }
+}
+
+/// Assembles `source` into a ROM for `Interpreter::load`, so labels resolve to
+/// addresses from 0x200 on.
+pub fn assemble(source: &str, syntax: Syntax) -> Result<Vec<u8>, AssembleError> {
+    match syntax {
+        Syntax::Octo => octo::assemble(source),
+        Syntax::Cowgod => cowgod::assemble(source),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{decode, disassembler::disassemble, Instruction, Interpreter};
+
+    fn listing(rom: &[u8], syntax: Syntax) -> String {
+        let mut lines = Vec::new();
+        for (_, word, instruction) in disassemble(rom, 0x200) {
+            lines.push(match (instruction, syntax) {
+                (Some(instruction), Syntax::Octo) => instruction.to_octo(),
+                (Some(instruction), Syntax::Cowgod) => instruction.to_string(),
+                (None, _) => format!(":byte 0x{:02X}\n:byte 0x{:02X}", word >> 8, word & 0xFF),
+            });
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_round_trip_every_instruction() {
+        let opcodes: Vec<u16> = (0..=u16::MAX)
+            .filter(|&opcode| decode(opcode).is_some() && opcode != 0xF000)
+            .collect();
+        for chunk in opcodes.chunks(1024) {
+            let rom: Vec<u8> = chunk
+                .iter()
+                .flat_map(|opcode| opcode.to_be_bytes())
+                .collect();
+            for syntax in [Syntax::Octo, Syntax::Cowgod] {
+                let source = listing(&rom, syntax);
+                assert_eq!(assemble(&source, syntax).unwrap(), rom);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_long_and_data() {
+        let rom = [0xF0, 0x00, 0xBE, 0xEF, 0xFF, 0xFF, 0x00, 0xEE];
+        for syntax in [Syntax::Octo, Syntax::Cowgod] {
+            let source = listing(&rom, syntax);
+            assert_eq!(assemble(&source, syntax).unwrap(), rom);
+        }
+    }
+
+    #[test]
+    fn test_output_runs() {
+        let rom = assemble(
+            "
+            : main
+                v0 := 7
+                i := hex v0
+                sprite v1 v1 5
+                loop again
+            ",
+            Syntax::Octo,
+        )
+        .unwrap();
+        assert_eq!(
+            decode(u16::from_be_bytes([rom[0], rom[1]])),
+            Some(Instruction::LdByte { x: 0, byte: 7 })
+        );
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&rom).unwrap();
+        for _ in 0..4 {
+            interpreter.tick().unwrap();
+        }
+        // the top row of the font sprite for 7 is 0xF0
+        let screen = interpreter.get_screen();
+        assert!(screen[0][..4].iter().all(|&pixel| pixel != 0));
+        assert!(screen[0][4..8].iter().all(|&pixel| pixel == 0));
+    }
+}
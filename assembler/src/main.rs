@@ -0,0 +1,68 @@
+use assembler::{assemble, Syntax};
+use std::{env, fs, path::Path, process};
+
+struct Options {
+    source_path: String,
+    output_path: Option<String>,
+    syntax: Syntax,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Option<Options> {
+        let mut source_path = None;
+        let mut output_path = None;
+        let mut syntax = Syntax::Octo;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--syntax" => syntax = Syntax::from_name(args.next()?)?,
+                "-o" => output_path = Some(args.next()?.clone()),
+                _ if source_path.is_none() => source_path = Some(arg.clone()),
+                _ => return None,
+            }
+        }
+
+        Some(Options {
+            source_path: source_path?,
+            output_path,
+            syntax,
+        })
+    }
+}
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    let Some(options) = Options::parse(&args[1..]) else {
+        println!("Usage: assembler [--syntax octo|cowgod] [-o <rom>] <source>");
+        return;
+    };
+
+    let source = match fs::read_to_string(&options.source_path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Unable to read {}: {err}", options.source_path);
+            process::exit(1);
+        }
+    };
+
+    let rom = match assemble(&source, options.syntax) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("{}:{err}", options.source_path);
+            process::exit(1);
+        }
+    };
+
+    let output_path = options.output_path.unwrap_or_else(|| {
+        Path::new(&options.source_path)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+    if let Err(err) = fs::write(&output_path, &rom) {
+        eprintln!("Unable to write {output_path}: {err}");
+        process::exit(1);
+    }
+    println!("Wrote {} bytes to {output_path}", rom.len());
+}
@@ -0,0 +1,576 @@
+use std::iter;
+
+use core::Instruction;
+
+use crate::program::{parse_number, Position, Program};
+use crate::AssembleError;
+
+struct Token<'a> {
+    text: &'a str,
+    position: Position,
+}
+
+/// An open `begin`, `else` or `loop` block waiting for its closing keyword.
+enum Flow {
+    If {
+        jump: usize,
+        position: Position,
+    },
+    Else {
+        jump: usize,
+        position: Position,
+    },
+    Loop {
+        start: usize,
+        breaks: Vec<usize>,
+        position: Position,
+    },
+}
+
+struct Parser<'a> {
+    tokens: std::vec::IntoIter<Token<'a>>,
+    last: Position,
+    program: Program,
+    flow: Vec<Flow>,
+}
+
+pub(crate) fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let mut parser = Parser {
+        tokens: tokenize(source).into_iter(),
+        last: Position { line: 1, column: 1 },
+        program: Program::default(),
+        flow: Vec::new(),
+    };
+
+    while let Some(token) = parser.tokens.next() {
+        parser.last = token.position;
+        parser.statement(token)?;
+    }
+
+    if let Some(flow) = parser.flow.pop() {
+        return Err(match flow {
+            Flow::If { position, .. } | Flow::Else { position, .. } => {
+                position.error("`begin` without `end`")
+            }
+            Flow::Loop { position, .. } => position.error("`loop` without `again`"),
+        });
+    }
+    parser.program.finish()
+}
+
+/// Splits on whitespace. `#` starts a comment that runs to the end of the line.
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or_default();
+        let mut start = None;
+        for (column, c) in code.char_indices().chain(iter::once((code.len(), ' '))) {
+            if !c.is_whitespace() {
+                start.get_or_insert(column);
+            } else if let Some(start) = start.take() {
+                tokens.push(Token {
+                    text: &code[start..column],
+                    position: Position {
+                        line: index + 1,
+                        column: start + 1,
+                    },
+                });
+            }
+        }
+    }
+    tokens
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<Token<'a>, AssembleError> {
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| self.last.error("unexpected end of source"))?;
+        self.last = token.position;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.as_slice().first().map(|token| token.text)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(token
+                .position
+                .error(format!("expected `{text}`, found `{}`", token.text)));
+        }
+        Ok(())
+    }
+
+    fn register(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        self.program.register(token.text, token.position)
+    }
+
+    fn byte(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        self.program.byte(token.text, token.position)
+    }
+
+    fn nibble(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        self.program.nibble(token.text, token.position)
+    }
+
+    fn addressed(&mut self, instruction: fn(u16) -> Instruction) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        self.program
+            .emit_addressed(instruction, token.text, token.position)
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> Result<(), AssembleError> {
+        self.program.emit(instruction);
+        Ok(())
+    }
+
+    /// Emits a jump whose target is patched in later, returning its offset.
+    fn forward_jump(&mut self) -> usize {
+        let offset = self.program.offset();
+        self.program.emit(Instruction::Jp(0));
+        offset
+    }
+
+    fn statement(&mut self, token: Token<'a>) -> Result<(), AssembleError> {
+        let position = token.position;
+        match token.text {
+            ":" => {
+                let name = self.next()?;
+                self.program.define_label(name.text, name.position)
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.next()?;
+                let value = self.program.known(value.text, value.position)?;
+                self.program
+                    .define_constant(name.text, value, name.position)
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.program
+                    .define_alias(name.text, register, name.position)
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.program.emit_byte(byte);
+                Ok(())
+            }
+            ":call" => self.addressed(Instruction::Call),
+            "clear" => self.emit(Instruction::Cls),
+            "return" | ";" => self.emit(Instruction::Ret),
+            "exit" => self.emit(Instruction::Exit),
+            "audio" => self.emit(Instruction::Audio),
+            "lores" => self.emit(Instruction::Lores),
+            "hires" => self.emit(Instruction::Hires),
+            "scroll-left" => self.emit(Instruction::ScrollLeft),
+            "scroll-right" => self.emit(Instruction::ScrollRight),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::ScrollDown(n))
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::ScrollUp(n))
+            }
+            "jump" => self.addressed(Instruction::Jp),
+            "jump0" => self.addressed(Instruction::JpV0),
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(Instruction::Drw { x, y, n })
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(Instruction::LdB(x))
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::SaveFlags(x))
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::LoadFlags(x))
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                let save = token.text == "save";
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    self.emit(if save {
+                        Instruction::SaveRange { x, y }
+                    } else {
+                        Instruction::LoadRange { x, y }
+                    })
+                } else {
+                    self.emit(if save {
+                        Instruction::LdMem(x)
+                    } else {
+                        Instruction::LdRegs(x)
+                    })
+                }
+            }
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::Plane(n))
+            }
+            "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(Instruction::Pitch(x))
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(if token.text == "delay" {
+                    Instruction::LdDtVx(x)
+                } else {
+                    Instruction::LdStVx(x)
+                })
+            }
+            "i" => self.index_assignment(),
+            "if" => {
+                let (when_true, when_false) = self.condition()?;
+                let keyword = self.next()?;
+                match keyword.text {
+                    "then" => self.emit(when_true),
+                    "begin" => {
+                        self.program.emit(when_false);
+                        let jump = self.forward_jump();
+                        self.flow.push(Flow::If { jump, position });
+                        Ok(())
+                    }
+                    other => Err(keyword
+                        .position
+                        .error(format!("expected `then` or `begin`, found `{other}`"))),
+                }
+            }
+            "else" => match self.flow.pop() {
+                Some(Flow::If { jump, .. }) => {
+                    let else_jump = self.forward_jump();
+                    let address = self.program.address();
+                    self.program.patch_jump(jump, address, position)?;
+                    self.flow.push(Flow::Else {
+                        jump: else_jump,
+                        position,
+                    });
+                    Ok(())
+                }
+                _ => Err(position.error("`else` without `if ... begin`")),
+            },
+            "end" => match self.flow.pop() {
+                Some(Flow::If { jump, .. } | Flow::Else { jump, .. }) => {
+                    let address = self.program.address();
+                    self.program.patch_jump(jump, address, position)
+                }
+                _ => Err(position.error("`end` without `begin`")),
+            },
+            "loop" => {
+                self.flow.push(Flow::Loop {
+                    start: self.program.address(),
+                    breaks: Vec::new(),
+                    position,
+                });
+                Ok(())
+            }
+            "while" => {
+                let (_, when_false) = self.condition()?;
+                self.program.emit(when_false);
+                let jump = self.forward_jump();
+                let innermost_loop = self.flow.iter_mut().rev().find_map(|flow| match flow {
+                    Flow::Loop { breaks, .. } => Some(breaks),
+                    _ => None,
+                });
+                match innermost_loop {
+                    Some(breaks) => {
+                        breaks.push(jump);
+                        Ok(())
+                    }
+                    None => Err(position.error("`while` outside of a loop")),
+                }
+            }
+            "again" => match self.flow.pop() {
+                Some(Flow::Loop { start, breaks, .. }) => {
+                    let jump = self.forward_jump();
+                    self.program.patch_jump(jump, start, position)?;
+                    let address = self.program.address();
+                    for jump in breaks {
+                        self.program.patch_jump(jump, address, position)?;
+                    }
+                    Ok(())
+                }
+                _ => Err(position.error("`again` without `loop`")),
+            },
+            text if self.program.is_register(text) => {
+                let x = self.program.register(text, position)?;
+                self.register_assignment(x)
+            }
+            text if parse_number(text).is_some() => {
+                let byte = self.program.byte(text, position)?;
+                self.program.emit_byte(byte);
+                Ok(())
+            }
+            // a bare name calls the subroutine with that label
+            text => self
+                .program
+                .emit_addressed(Instruction::Call, text, position),
+        }
+    }
+
+    fn index_assignment(&mut self) -> Result<(), AssembleError> {
+        let operator = self.next()?;
+        match operator.text {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.emit(Instruction::LdF(x))
+                }
+                Some("bighex") => {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.emit(Instruction::LdHf(x))
+                }
+                Some("long") => {
+                    self.next()?;
+                    let address = self.next()?;
+                    self.program.emit_long(address.text, address.position)
+                }
+                _ => self.addressed(Instruction::LdI),
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.emit(Instruction::AddI(x))
+            }
+            other => Err(operator
+                .position
+                .error(format!("unknown operator `{other}` for `i`"))),
+        }
+    }
+
+    fn register_assignment(&mut self, x: u8) -> Result<(), AssembleError> {
+        let operator = self.next()?;
+        let instruction = match operator.text {
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    Instruction::Rnd {
+                        x,
+                        byte: self.byte()?,
+                    }
+                }
+                Some("delay") => {
+                    self.next()?;
+                    Instruction::LdVxDt(x)
+                }
+                Some("key") => {
+                    self.next()?;
+                    Instruction::LdVxK(x)
+                }
+                _ => {
+                    let source = self.next()?;
+                    if self.program.is_register(source.text) {
+                        let y = self.program.register(source.text, source.position)?;
+                        Instruction::LdReg { x, y }
+                    } else {
+                        let byte = self.program.byte(source.text, source.position)?;
+                        Instruction::LdByte { x, byte }
+                    }
+                }
+            },
+            "+=" | "-=" => {
+                let source = self.next()?;
+                let add = operator.text == "+=";
+                if self.program.is_register(source.text) {
+                    let y = self.program.register(source.text, source.position)?;
+                    if add {
+                        Instruction::AddReg { x, y }
+                    } else {
+                        Instruction::Sub { x, y }
+                    }
+                } else {
+                    let byte = self.program.byte(source.text, source.position)?;
+                    let byte = if add { byte } else { byte.wrapping_neg() };
+                    Instruction::AddByte { x, byte }
+                }
+            }
+            "=-" => Instruction::Subn {
+                x,
+                y: self.register()?,
+            },
+            "|=" => Instruction::Or {
+                x,
+                y: self.register()?,
+            },
+            "&=" => Instruction::And {
+                x,
+                y: self.register()?,
+            },
+            "^=" => Instruction::Xor {
+                x,
+                y: self.register()?,
+            },
+            ">>=" => Instruction::Shr {
+                x,
+                y: self.register()?,
+            },
+            "<<=" => Instruction::Shl {
+                x,
+                y: self.register()?,
+            },
+            other => {
+                return Err(operator
+                    .position
+                    .error(format!("unknown operator `{other}`")))
+            }
+        };
+        self.emit(instruction)
+    }
+
+    /// Parses `vx == n`, `vx != vy`, `vx key` and the like. Returns the skip after
+    /// which the next instruction runs only if the condition holds, and the skip after
+    /// which it runs only if it does not.
+    fn condition(&mut self) -> Result<(Instruction, Instruction), AssembleError> {
+        let x = self.register()?;
+        let operator = self.next()?;
+        match operator.text {
+            "key" => Ok((Instruction::Sknp(x), Instruction::Skp(x))),
+            "-key" => Ok((Instruction::Skp(x), Instruction::Sknp(x))),
+            "==" | "!=" => {
+                let operand = self.next()?;
+                let (equal, not_equal) = if self.program.is_register(operand.text) {
+                    let y = self.program.register(operand.text, operand.position)?;
+                    (Instruction::SneReg { x, y }, Instruction::SeReg { x, y })
+                } else {
+                    let byte = self.program.byte(operand.text, operand.position)?;
+                    (
+                        Instruction::SneByte { x, byte },
+                        Instruction::SeByte { x, byte },
+                    )
+                };
+                if operator.text == "==" {
+                    Ok((equal, not_equal))
+                } else {
+                    Ok((not_equal, equal))
+                }
+            }
+            other => Err(operator
+                .position
+                .error(format!("unsupported comparison `{other}`"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statements() {
+        let rom = assemble(
+            "
+            : main
+                clear
+                v0 := 5
+                v1 += v0
+                v2 -= 1
+                i := hex v0
+                sprite v1 v2 5
+                if v0 == 5 then v3 := random 0xFF
+                jump main
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            rom,
+            [
+                0x00, 0xE0, 0x60, 0x05, 0x81, 0x04, 0x72, 0xFF, 0xF0, 0x29, 0xD1, 0x25, 0x40, 0x05,
+                0xC3, 0xFF, 0x12, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels_constants_and_aliases() {
+        let rom = assemble(
+            "
+            :const SPEED 3
+            :alias x v4
+            x := SPEED
+            i := sprite
+            draw
+            : draw
+                return
+            : sprite
+                :byte 0b11110000
+                0x90
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            rom,
+            [0x64, 0x03, 0xA2, 0x08, 0x22, 0x06, 0x00, 0xEE, 0xF0, 0x90]
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let rom = assemble(
+            "
+            loop
+                while v0 != 10
+                if v1 == v2 begin
+                    v0 += 1
+                else
+                    v0 += 2
+                end
+            again
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            rom,
+            [
+                0x40, 0x0A, 0x12, 0x10, // while: skip the break unless v0 != 10
+                0x51, 0x20, 0x12, 0x0C, // if: jump to else unless v1 == v2
+                0x70, 0x01, 0x12, 0x0E, // then, jump over else
+                0x70, 0x02, // else
+                0x12, 0x00, // again
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_and_ranges() {
+        let rom = assemble("i := long data save v1 - v3 plane 3 : data").unwrap();
+        assert_eq!(rom, [0xF0, 0x00, 0x02, 0x08, 0x51, 0x32, 0xF3, 0x01]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = assemble("v0 := 1\n  jump nowhere").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 8));
+        assert_eq!(error.message, "`nowhere` is not defined");
+
+        let error = assemble("v0 := vx").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 7));
+
+        let error = assemble("v0 := 300").unwrap_err();
+        assert_eq!(error.message, "`300` does not fit in a byte");
+
+        let error = assemble("loop v0 += 1").unwrap_err();
+        assert_eq!(error.message, "`loop` without `again`");
+
+        let error = assemble(": a : a").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 7));
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use core::Instruction;
+
+use crate::AssembleError;
+
+/// Where `Interpreter::load` places a ROM.
+pub(crate) const ORIGIN: usize = 0x200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn error(self, message: impl Into<String>) -> AssembleError {
+        AssembleError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+pub(crate) enum Value {
+    Known(i64),
+    /// A name that is not defined yet, assumed to be a label further down.
+    Forward(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Address12,
+    Address16,
+}
+
+struct Fixup {
+    offset: usize,
+    slot: Slot,
+    name: String,
+    position: Position,
+}
+
+/// The ROM being built, shared by both syntaxes. Constants and aliases must be
+/// defined before use, labels may be referenced before they are defined.
+#[derive(Default)]
+pub(crate) struct Program {
+    bytes: Vec<u8>,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    fixups: Vec<Fixup>,
+}
+
+impl Program {
+    pub fn address(&self) -> usize {
+        ORIGIN + self.bytes.len()
+    }
+
+    pub fn offset(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn define_label(&mut self, name: &str, position: Position) -> Result<(), AssembleError> {
+        self.check_new_name(name, position)?;
+        self.labels.insert(name.to_string(), self.address());
+        Ok(())
+    }
+
+    pub fn define_constant(
+        &mut self,
+        name: &str,
+        value: i64,
+        position: Position,
+    ) -> Result<(), AssembleError> {
+        self.check_new_name(name, position)?;
+        self.constants.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    pub fn define_alias(
+        &mut self,
+        name: &str,
+        register: u8,
+        position: Position,
+    ) -> Result<(), AssembleError> {
+        self.check_new_name(name, position)?;
+        self.aliases.insert(name.to_string(), register);
+        Ok(())
+    }
+
+    fn check_new_name(&self, name: &str, position: Position) -> Result<(), AssembleError> {
+        if !is_identifier(name) || parse_register(name).is_some() {
+            return Err(position.error(format!("`{name}` is not a valid name")));
+        }
+        if self.labels.contains_key(name)
+            || self.constants.contains_key(name)
+            || self.aliases.contains_key(name)
+        {
+            return Err(position.error(format!("`{name}` is already defined")));
+        }
+        Ok(())
+    }
+
+    pub fn is_register(&self, text: &str) -> bool {
+        parse_register(text).is_some() || self.aliases.contains_key(text)
+    }
+
+    pub fn register(&self, text: &str, position: Position) -> Result<u8, AssembleError> {
+        parse_register(text)
+            .or_else(|| self.aliases.get(text).copied())
+            .ok_or_else(|| position.error(format!("expected a register, found `{text}`")))
+    }
+
+    pub fn value(&self, text: &str, position: Position) -> Result<Value, AssembleError> {
+        if let Some(number) = parse_number(text) {
+            return Ok(Value::Known(number));
+        }
+        if let Some(&value) = self.constants.get(text) {
+            return Ok(Value::Known(value));
+        }
+        if let Some(&address) = self.labels.get(text) {
+            return Ok(Value::Known(address as i64));
+        }
+        if is_identifier(text) && !self.is_register(text) {
+            return Ok(Value::Forward(text.to_string()));
+        }
+        Err(position.error(format!("expected a number or name, found `{text}`")))
+    }
+
+    pub fn known(&self, text: &str, position: Position) -> Result<i64, AssembleError> {
+        match self.value(text, position)? {
+            Value::Known(value) => Ok(value),
+            Value::Forward(name) => Err(position.error(format!("`{name}` is not defined"))),
+        }
+    }
+
+    pub fn byte(&self, text: &str, position: Position) -> Result<u8, AssembleError> {
+        let value = self.known(text, position)?;
+        if !(-128..=255).contains(&value) {
+            return Err(position.error(format!("`{text}` does not fit in a byte")));
+        }
+        Ok(value as u8)
+    }
+
+    pub fn nibble(&self, text: &str, position: Position) -> Result<u8, AssembleError> {
+        let value = self.known(text, position)?;
+        if !(0..=15).contains(&value) {
+            return Err(position.error(format!("`{text}` does not fit in a nibble")));
+        }
+        Ok(value as u8)
+    }
+
+    pub fn emit(&mut self, instruction: Instruction) {
+        self.bytes
+            .extend_from_slice(&instruction.encode().to_be_bytes());
+        if let Instruction::LdILong(address) = instruction {
+            self.bytes.extend_from_slice(&address.to_be_bytes());
+        }
+    }
+
+    pub fn emit_byte(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    /// Emits an instruction with a 12-bit address operand, which may name a label
+    /// that is defined later.
+    pub fn emit_addressed(
+        &mut self,
+        instruction: fn(u16) -> Instruction,
+        text: &str,
+        position: Position,
+    ) -> Result<(), AssembleError> {
+        let offset = self.offset();
+        self.emit(instruction(0));
+        self.resolve(offset, Slot::Address12, text, position)
+    }
+
+    /// Emits `F000 NNNN`.
+    pub fn emit_long(&mut self, text: &str, position: Position) -> Result<(), AssembleError> {
+        let offset = self.offset();
+        self.emit(Instruction::LdILong(0));
+        self.resolve(offset + 2, Slot::Address16, text, position)
+    }
+
+    /// Points the jump emitted at `offset` to `address`.
+    pub fn patch_jump(
+        &mut self,
+        offset: usize,
+        address: usize,
+        position: Position,
+    ) -> Result<(), AssembleError> {
+        self.patch(offset, Slot::Address12, address, position)
+    }
+
+    fn resolve(
+        &mut self,
+        offset: usize,
+        slot: Slot,
+        text: &str,
+        position: Position,
+    ) -> Result<(), AssembleError> {
+        match self.value(text, position)? {
+            Value::Known(address) if address < 0 => {
+                Err(position.error(format!("address `{text}` is negative")))
+            }
+            Value::Known(address) => self.patch(offset, slot, address as usize, position),
+            Value::Forward(name) => {
+                self.fixups.push(Fixup {
+                    offset,
+                    slot,
+                    name,
+                    position,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn patch(
+        &mut self,
+        offset: usize,
+        slot: Slot,
+        address: usize,
+        position: Position,
+    ) -> Result<(), AssembleError> {
+        match slot {
+            Slot::Address12 => {
+                if address > 0xFFF {
+                    return Err(position.error(format!(
+                        "address {address:#06X} is out of reach of a 12-bit operand"
+                    )));
+                }
+                let word = u16::from_be_bytes([self.bytes[offset], self.bytes[offset + 1]]);
+                let word = (word & 0xF000) | address as u16;
+                self.bytes[offset..offset + 2].copy_from_slice(&word.to_be_bytes());
+            }
+            Slot::Address16 => {
+                if address > 0xFFFF {
+                    return Err(position.error(format!(
+                        "address {address:#X} is out of reach of a 16-bit operand"
+                    )));
+                }
+                self.bytes[offset..offset + 2].copy_from_slice(&(address as u16).to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<Vec<u8>, AssembleError> {
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&address) = self.labels.get(&fixup.name) else {
+                return Err(fixup
+                    .position
+                    .error(format!("`{}` is not defined", fixup.name)));
+            };
+            self.patch(fixup.offset, fixup.slot, address, fixup.position)?;
+        }
+        Ok(self.bytes)
+    }
+}
+
+/// `v0` to `vF`, in either case.
+pub(crate) fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/// Decimal, `0x` hexadecimal or `0b` binary, optionally negative.
+pub(crate) fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("42"), Some(42));
+        assert_eq!(parse_number("0x2A"), Some(42));
+        assert_eq!(parse_number("0b101010"), Some(42));
+        assert_eq!(parse_number("-1"), Some(-1));
+        assert_eq!(parse_number("v1"), None);
+        assert_eq!(parse_number("0xZZ"), None);
+    }
+
+    #[test]
+    fn test_parse_register() {
+        assert_eq!(parse_register("v0"), Some(0));
+        assert_eq!(parse_register("VF"), Some(0xF));
+        assert_eq!(parse_register("va"), Some(0xA));
+        assert_eq!(parse_register("vg"), None);
+        assert_eq!(parse_register("v10"), None);
+    }
+}
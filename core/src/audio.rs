@@ -0,0 +1,127 @@
+pub const DEFAULT_FREQUENCY: f32 = 440.0;
+pub(crate) const PATTERN_SIZE: usize = 16;
+const PATTERN_BITS: f64 = (PATTERN_SIZE * 8) as f64;
+/// The pitch register value at which a pattern plays at 4000 bits per second.
+pub(crate) const DEFAULT_PITCH: u8 = 64;
+
+/// The buzzer's sound source: a square tone, or XO-CHIP's 128-bit pattern once a
+/// ROM loads one with F002.
+#[derive(Debug, Clone)]
+pub(crate) struct Voice {
+    pub(crate) pattern: Option<[u8; PATTERN_SIZE]>,
+    pub(crate) pitch: u8,
+    pub(crate) frequency: f32,
+    /// Position within the tone's period or the pattern, from 0 to 1.
+    phase: f64,
+}
+
+impl Voice {
+    pub(crate) fn reset(&mut self) {
+        self.pattern = None;
+        self.pitch = DEFAULT_PITCH;
+        self.phase = 0.0;
+    }
+
+    /// Renders samples of -1 or 1 while `active`, and silence otherwise. The phase
+    /// carries over between calls so consecutive buffers join without clicks.
+    pub(crate) fn render(&mut self, active: bool, out: &mut [f32], sample_rate: u32) {
+        if !active || sample_rate == 0 {
+            self.phase = 0.0;
+            out.fill(0.0);
+            return;
+        }
+
+        let cycles_per_second = match self.pattern {
+            Some(_) => self.bit_rate() / PATTERN_BITS,
+            None => self.frequency as f64,
+        };
+        let step = cycles_per_second / sample_rate as f64;
+        for sample in out.iter_mut() {
+            let high = match &self.pattern {
+                Some(pattern) => {
+                    let bit = (self.phase * PATTERN_BITS) as usize;
+                    pattern[bit / 8] >> (7 - bit % 8) & 1 != 0
+                }
+                None => self.phase < 0.5,
+            };
+            *sample = if high { 1.0 } else { -1.0 };
+            self.phase = (self.phase + step).fract();
+        }
+    }
+
+    /// Pattern bits per second: 4000 at the default pitch, doubling every 48 steps.
+    fn bit_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - DEFAULT_PITCH as f64) / 48.0)
+    }
+}
+
+impl Default for Voice {
+    fn default() -> Self {
+        Voice {
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+            frequency: DEFAULT_FREQUENCY,
+            phase: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_wave() {
+        let mut voice = Voice {
+            frequency: 100.0,
+            ..Voice::default()
+        };
+        let mut samples = [0.0; 16];
+        voice.render(true, &mut samples, 800);
+        assert_eq!(samples[..8], [1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0]);
+        assert_eq!(samples[..8], samples[8..]);
+    }
+
+    #[test]
+    fn test_phase_continues_across_calls() {
+        let mut whole = [0.0; 300];
+        Voice::default().render(true, &mut whole, 8000);
+
+        let mut voice = Voice::default();
+        let mut parts = [0.0; 300];
+        voice.render(true, &mut parts[..77], 8000);
+        voice.render(true, &mut parts[77..], 8000);
+        assert_eq!(whole, parts);
+    }
+
+    #[test]
+    fn test_pattern() {
+        let mut pattern = [0; PATTERN_SIZE];
+        pattern[0] = 0b1010_0000;
+        let mut voice = Voice {
+            pattern: Some(pattern),
+            ..Voice::default()
+        };
+        // at the default pitch one sample is one bit
+        let mut samples = [0.0; 130];
+        voice.render(true, &mut samples, 4000);
+        assert_eq!(samples[..4], [1.0, -1.0, 1.0, -1.0]);
+        assert!(samples[4..128].iter().all(|&sample| sample == -1.0));
+        assert_eq!(samples[128..], [1.0, -1.0]);
+
+        // 48 steps up is an octave: two bits per sample
+        voice.reset();
+        voice.pattern = Some(pattern);
+        voice.pitch = DEFAULT_PITCH + 48;
+        voice.render(true, &mut samples[..2], 4000);
+        assert_eq!(samples[..2], [1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_silence() {
+        let mut voice = Voice::default();
+        let mut samples = [1.0; 8];
+        voice.render(false, &mut samples, 8000);
+        assert_eq!(samples, [0.0; 8]);
+    }
+}
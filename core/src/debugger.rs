@@ -0,0 +1,711 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{decode, Chip8Error, ConditionError, FrameSummary, Instruction, Interpreter, Memory};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A register that can be watched for changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+}
+
+impl Register {
+    fn value(self, interpreter: &Interpreter) -> u16 {
+        match self {
+            Register::V(x) => interpreter.registers[x as usize & 0xF] as u16,
+            Register::I => interpreter.index_register,
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{x:X}"),
+            Register::I => write!(f, "I"),
+        }
+    }
+}
+
+/// Why execution stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// About to execute the instruction at a breakpoint.
+    Breakpoint(u16),
+    /// About to execute an instruction while the condition with this index holds.
+    Condition(usize),
+    /// The last instruction touched a watched address.
+    Watchpoint {
+        address: u16,
+        access: Access,
+    },
+    /// The last instruction changed a watched register.
+    RegisterChanged {
+        register: Register,
+        old: u16,
+        new: u16,
+    },
+    /// The step, step-over, step-out or run-until request completed.
+    Done,
+    Halted,
+    Fault(Chip8Error),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(address) => write!(f, "breakpoint at {address:04X}"),
+            Stop::Condition(index) => write!(f, "condition {index} holds"),
+            Stop::Watchpoint { address, access } => {
+                let verb = match access {
+                    Access::Read => "read",
+                    Access::Write => "write",
+                };
+                write!(f, "{verb} of watched address {address:04X}")
+            }
+            Stop::RegisterChanged { register, old, new } => {
+                write!(f, "{register} changed from {old:02X} to {new:02X}")
+            }
+            Stop::Done => write!(f, "done"),
+            Stop::Halted => write!(f, "halted"),
+            Stop::Fault(err) => write!(f, "fault: {err}"),
+        }
+    }
+}
+
+/// Execution control around an `Interpreter`: breakpoints, watchpoints and stepping.
+///
+/// Every run takes an instruction budget and returns `None` once it is spent, so a
+/// frontend can keep calling it once per frame.
+#[derive(Debug, Clone, Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    conditions: Vec<(Option<u16>, Condition)>,
+    watchpoints: Vec<(u16, Access)>,
+    watched_registers: Vec<Register>,
+    /// Where execution last stopped, so that resuming does not stop there again.
+    resume_at: Option<u16>,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Self::default()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Stops when `condition` holds, either anywhere or only at `address`.
+    pub fn add_condition(&mut self, address: Option<u16>, condition: Condition) -> usize {
+        self.conditions.push((address, condition));
+        self.conditions.len() - 1
+    }
+
+    pub fn remove_condition(&mut self, index: usize) -> bool {
+        if index < self.conditions.len() {
+            self.conditions.remove(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn conditions(&self) -> &[(Option<u16>, Condition)] {
+        &self.conditions
+    }
+
+    pub fn add_watchpoint(&mut self, address: u16, access: Access) {
+        if !self.watchpoints.contains(&(address, access)) {
+            self.watchpoints.push((address, access));
+        }
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u16, access: Access) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|&watch| watch != (address, access));
+        self.watchpoints.len() != count
+    }
+
+    pub fn watchpoints(&self) -> &[(u16, Access)] {
+        &self.watchpoints
+    }
+
+    pub fn watch_register(&mut self, register: Register) {
+        if !self.watched_registers.contains(&register) {
+            self.watched_registers.push(register);
+        }
+    }
+
+    pub fn unwatch_register(&mut self, register: Register) -> bool {
+        let count = self.watched_registers.len();
+        self.watched_registers
+            .retain(|&watched| watched != register);
+        self.watched_registers.len() != count
+    }
+
+    pub fn watched_registers(&self) -> &[Register] {
+        &self.watched_registers
+    }
+
+    /// Executes a single instruction, ignoring breakpoints.
+    pub fn step(&mut self, interpreter: &mut Interpreter) -> Stop {
+        let stop = self.execute(interpreter).unwrap_or(Stop::Done);
+        self.resume_at = Some(interpreter.program_counter);
+        stop
+    }
+
+    /// Runs until something stops execution.
+    pub fn run(&mut self, interpreter: &mut Interpreter, budget: usize) -> Option<Stop> {
+        self.run_until(interpreter, budget, |_| false)
+    }
+
+    /// Runs one frame like `Interpreter::run_frame`, unless something stops execution
+    /// first. The timers are only decremented once the frame completes.
+    pub fn run_frame(&mut self, interpreter: &mut Interpreter) -> Result<FrameSummary, Stop> {
+        interpreter.begin_frame();
+        let mut instructions = 0;
+        let mut fault = None;
+        while !interpreter.is_frame_over(instructions) {
+            match self
+                .check_breakpoints(interpreter)
+                .or_else(|| self.execute(interpreter))
+            {
+                None => instructions += 1,
+                Some(Stop::Fault(err)) => {
+                    fault = Some(err);
+                    break;
+                }
+                Some(stop) => {
+                    self.resume_at = Some(interpreter.program_counter);
+                    return Err(stop);
+                }
+            }
+        }
+        Ok(interpreter.end_frame(instructions, fault))
+    }
+
+    /// Like `step`, but runs a whole subroutine when the next instruction is a call.
+    pub fn step_over(&mut self, interpreter: &mut Interpreter, budget: usize) -> Option<Stop> {
+        let pc = interpreter.program_counter;
+        let is_call = matches!(
+            interpreter.mem_read_16(pc).ok().and_then(decode),
+            Some(Instruction::Call(_))
+        );
+        if !is_call {
+            return Some(self.step(interpreter));
+        }
+
+        let depth = interpreter.stack_pointer;
+        let return_address = pc.wrapping_add(2);
+        self.run_until(interpreter, budget, |interpreter| {
+            interpreter.stack_pointer == depth && interpreter.program_counter == return_address
+        })
+    }
+
+    /// Runs until the current subroutine returns.
+    pub fn step_out(&mut self, interpreter: &mut Interpreter, budget: usize) -> Option<Stop> {
+        let depth = interpreter.stack_pointer;
+        self.run_until(interpreter, budget, |interpreter| {
+            interpreter.stack_pointer < depth
+        })
+    }
+
+    /// Runs until the program counter reaches `address`.
+    pub fn run_to(
+        &mut self,
+        interpreter: &mut Interpreter,
+        address: u16,
+        budget: usize,
+    ) -> Option<Stop> {
+        self.run_until(interpreter, budget, |interpreter| {
+            interpreter.program_counter == address
+        })
+    }
+
+    fn run_until(
+        &mut self,
+        interpreter: &mut Interpreter,
+        budget: usize,
+        done: impl Fn(&Interpreter) -> bool,
+    ) -> Option<Stop> {
+        let stop = self.run_for(interpreter, budget, done);
+        if stop.is_some() {
+            // resuming from here must not stop at the same breakpoint again
+            self.resume_at = Some(interpreter.program_counter);
+        }
+        stop
+    }
+
+    fn run_for(
+        &mut self,
+        interpreter: &mut Interpreter,
+        budget: usize,
+        done: impl Fn(&Interpreter) -> bool,
+    ) -> Option<Stop> {
+        for _ in 0..budget {
+            if let Some(stop) = self.check_breakpoints(interpreter) {
+                return Some(stop);
+            }
+            if let Some(stop) = self.execute(interpreter) {
+                return Some(stop);
+            }
+            if done(interpreter) {
+                return Some(Stop::Done);
+            }
+        }
+        None
+    }
+
+    fn check_breakpoints(&mut self, interpreter: &Interpreter) -> Option<Stop> {
+        let pc = interpreter.program_counter;
+        if self.resume_at.take() == Some(pc) {
+            return None;
+        }
+        if self.breakpoints.contains(&pc) {
+            return Some(Stop::Breakpoint(pc));
+        }
+        self.conditions
+            .iter()
+            .position(|(address, condition)| {
+                address.is_none_or(|address| address == pc) && condition.holds(interpreter)
+            })
+            .map(Stop::Condition)
+    }
+
+    fn execute(&mut self, interpreter: &mut Interpreter) -> Option<Stop> {
+        if interpreter.halted {
+            return Some(Stop::Halted);
+        }
+
+        let accesses = memory_accesses(interpreter);
+        let watched = self
+            .watchpoints
+            .iter()
+            .find(|&&(address, access)| {
+                accesses.is_some_and(|(kind, start, len)| {
+                    kind == access && (start..start + len).contains(&(address as usize))
+                })
+            })
+            .copied();
+        let before: Vec<u16> = self
+            .watched_registers
+            .iter()
+            .map(|register| register.value(interpreter))
+            .collect();
+
+        if let Err(err) = interpreter.tick() {
+            return Some(Stop::Fault(err));
+        }
+
+        if let Some((address, access)) = watched {
+            return Some(Stop::Watchpoint { address, access });
+        }
+        for (&register, &old) in self.watched_registers.iter().zip(&before) {
+            let new = register.value(interpreter);
+            if new != old {
+                return Some(Stop::RegisterChanged { register, old, new });
+            }
+        }
+        if interpreter.halted {
+            return Some(Stop::Halted);
+        }
+        None
+    }
+}
+
+/// The data memory the next instruction will touch, as (access, start, length).
+fn memory_accesses(interpreter: &Interpreter) -> Option<(Access, usize, usize)> {
+    let opcode = interpreter.mem_read_16(interpreter.program_counter).ok()?;
+    let register_count = |x: u8| x as usize + 1;
+    let (access, len) = match decode(opcode)? {
+        Instruction::Drw { n, .. } => {
+            let bytes_per_plane = if n == 0 { 32 } else { n as usize };
+            let planes = interpreter.planes.count_ones() as usize;
+            (Access::Read, bytes_per_plane * planes)
+        }
+        Instruction::LdB(_) => (Access::Write, 3),
+        Instruction::LdMem(x) => (Access::Write, register_count(x)),
+        Instruction::LdRegs(x) => (Access::Read, register_count(x)),
+        Instruction::SaveRange { x, y } if interpreter.xo_chip => {
+            (Access::Write, x.abs_diff(y) as usize + 1)
+        }
+        Instruction::LoadRange { x, y } if interpreter.xo_chip => {
+            (Access::Read, x.abs_diff(y) as usize + 1)
+        }
+        Instruction::Audio if interpreter.xo_chip => (Access::Read, 16),
+        _ => return None,
+    };
+    Some((access, interpreter.index_register as usize, len))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Number(u16),
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    /// The byte at the address given by the inner operand.
+    Memory(Value),
+}
+
+/// An operand that can be used as an address inside `[...]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Number(u16),
+    V(u8),
+    I,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// A breakpoint condition such as `v3 == 0x10 && [i] != 0`.
+///
+/// Operands are numbers, `v0`-`vf`, `i`, `pc`, `sp`, `dt`, `st` and memory bytes
+/// written as `[0x300]`, `[i]` or `[v2]`. Clauses are joined with `&&`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    text: String,
+    clauses: Vec<(Operand, Comparison, Operand)>,
+}
+
+impl Condition {
+    pub fn holds(&self, interpreter: &Interpreter) -> bool {
+        self.clauses.iter().all(|&(left, comparison, right)| {
+            let (left, right) = (left.value(interpreter), right.value(interpreter));
+            match comparison {
+                Comparison::Equal => left == right,
+                Comparison::NotEqual => left != right,
+                Comparison::Less => left < right,
+                Comparison::LessOrEqual => left <= right,
+                Comparison::Greater => left > right,
+                Comparison::GreaterOrEqual => left >= right,
+            }
+        })
+    }
+}
+
+impl Operand {
+    fn value(self, interpreter: &Interpreter) -> u16 {
+        match self {
+            Operand::Number(value) => value,
+            Operand::V(x) => interpreter.registers[x as usize] as u16,
+            Operand::I => interpreter.index_register,
+            Operand::Pc => interpreter.program_counter,
+            Operand::Sp => interpreter.stack_pointer as u16,
+            Operand::Dt => interpreter.delay_timer as u16,
+            Operand::St => interpreter.sound_timer as u16,
+            Operand::Memory(address) => {
+                let address = match address {
+                    Value::Number(address) => address,
+                    Value::V(x) => interpreter.registers[x as usize] as u16,
+                    Value::I => interpreter.index_register,
+                };
+                interpreter.mem_read(address).unwrap_or(0) as u16
+            }
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let clauses = text
+            .split("&&")
+            .map(parse_clause)
+            .collect::<Result<_, _>>()?;
+        Ok(Condition {
+            text: text.trim().to_string(),
+            clauses,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+fn condition_error(message: String) -> ConditionError {
+    ConditionError { message }
+}
+
+fn parse_clause(text: &str) -> Result<(Operand, Comparison, Operand), ConditionError> {
+    const COMPARISONS: [(&str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+    let (symbol, comparison) = COMPARISONS
+        .iter()
+        .find(|(symbol, _)| text.contains(symbol))
+        .ok_or_else(|| condition_error(format!("no comparison in `{}`", text.trim())))?;
+    let (left, right) = text.split_once(symbol).unwrap();
+    Ok((parse_operand(left)?, *comparison, parse_operand(right)?))
+}
+
+fn parse_operand(text: &str) -> Result<Operand, ConditionError> {
+    let text = text.trim();
+    if let Some(inner) = text
+        .strip_prefix('[')
+        .and_then(|text| text.strip_suffix(']'))
+    {
+        let address = match parse_operand(inner)? {
+            Operand::Number(address) => Value::Number(address),
+            Operand::V(x) => Value::V(x),
+            Operand::I => Value::I,
+            _ => {
+                return Err(condition_error(format!(
+                    "`{inner}` cannot be used as an address"
+                )))
+            }
+        };
+        return Ok(Operand::Memory(address));
+    }
+
+    let lower = text.to_ascii_lowercase();
+    match lower.as_str() {
+        "i" => return Ok(Operand::I),
+        "pc" => return Ok(Operand::Pc),
+        "sp" => return Ok(Operand::Sp),
+        "dt" => return Ok(Operand::Dt),
+        "st" => return Ok(Operand::St),
+        _ => {}
+    }
+    if let Some(digit) = lower.strip_prefix('v').filter(|digit| digit.len() == 1) {
+        if let Ok(x) = u8::from_str_radix(digit, 16) {
+            return Ok(Operand::V(x));
+        }
+    }
+    parse_number(&lower)
+        .map(Operand::Number)
+        .ok_or_else(|| condition_error(format!("unknown operand `{text}`")))
+}
+
+/// Decimal or `0x` hexadecimal.
+pub fn parse_number(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 200: V0 := 3       202: call 208    204: V1 += 1     206: jump 204
+    // 208: I := 300      20A: save V0     20C: return
+    const PROGRAM: [u8; 14] = [
+        0x60, 0x03, 0x22, 0x08, 0x71, 0x01, 0x12, 0x04, 0xA3, 0x00, 0xF0, 0x55, 0x00, 0xEE,
+    ];
+
+    fn interpreter() -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&PROGRAM).unwrap();
+        interpreter
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x204);
+
+        assert_eq!(
+            debugger.run(&mut interpreter, 100),
+            Some(Stop::Breakpoint(0x204))
+        );
+        assert_eq!(interpreter.program_counter(), 0x204);
+
+        // resuming runs past the breakpoint and stops there on the next lap
+        assert_eq!(
+            debugger.run(&mut interpreter, 100),
+            Some(Stop::Breakpoint(0x204))
+        );
+        assert_eq!(interpreter.registers()[1], 1);
+
+        assert!(debugger.remove_breakpoint(0x204));
+        assert_eq!(debugger.run(&mut interpreter, 10), None);
+    }
+
+    #[test]
+    fn test_breakpoint_across_budgets() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x208);
+
+        assert_eq!(debugger.run(&mut interpreter, 2), None);
+        assert_eq!(
+            debugger.run(&mut interpreter, 2),
+            Some(Stop::Breakpoint(0x208))
+        );
+    }
+
+    #[test]
+    fn test_run_frame_stops_before_timers() {
+        let mut interpreter = interpreter();
+        interpreter.delay_timer = 10;
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x208);
+
+        assert_eq!(
+            debugger.run_frame(&mut interpreter),
+            Err(Stop::Breakpoint(0x208))
+        );
+        assert_eq!(interpreter.delay_timer(), 10);
+
+        let summary = debugger.run_frame(&mut interpreter).unwrap();
+        assert_eq!(summary.instructions, 5);
+        assert_eq!(interpreter.delay_timer(), 9);
+    }
+
+    #[test]
+    fn test_write_watchpoint() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(0x300, Access::Write);
+        debugger.add_watchpoint(0x300, Access::Read);
+
+        assert_eq!(
+            debugger.run(&mut interpreter, 100),
+            Some(Stop::Watchpoint {
+                address: 0x300,
+                access: Access::Write
+            })
+        );
+        assert_eq!(interpreter.program_counter(), 0x20C);
+        assert_eq!(interpreter.memory()[0x300], 3);
+    }
+
+    #[test]
+    fn test_register_watch() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new();
+        debugger.watch_register(Register::I);
+
+        assert_eq!(
+            debugger.run(&mut interpreter, 100),
+            Some(Stop::RegisterChanged {
+                register: Register::I,
+                old: 0,
+                new: 0x300
+            })
+        );
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new();
+
+        assert_eq!(debugger.step(&mut interpreter), Stop::Done);
+        assert_eq!(debugger.step_over(&mut interpreter, 100), Some(Stop::Done));
+        assert_eq!(interpreter.program_counter(), 0x204);
+        assert_eq!(interpreter.memory()[0x300], 3);
+
+        let mut interpreter = self::interpreter();
+        debugger.step(&mut interpreter);
+        debugger.step(&mut interpreter);
+        assert_eq!(interpreter.program_counter(), 0x208);
+        assert_eq!(debugger.step_out(&mut interpreter, 100), Some(Stop::Done));
+        assert_eq!(interpreter.program_counter(), 0x204);
+        assert_eq!(interpreter.stack_pointer(), 0);
+    }
+
+    #[test]
+    fn test_continue_after_step_onto_breakpoint() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+        debugger.step(&mut interpreter);
+        assert_eq!(interpreter.program_counter(), 0x202);
+
+        assert_eq!(debugger.run(&mut interpreter, 3), None);
+    }
+
+    #[test]
+    fn test_run_to() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new();
+        assert_eq!(
+            debugger.run_to(&mut interpreter, 0x20A, 100),
+            Some(Stop::Done)
+        );
+        assert_eq!(interpreter.index_register(), 0x300);
+    }
+
+    #[test]
+    fn test_condition() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new();
+        let condition: Condition = "v1 >= 3 && [0x300] == 3".parse().unwrap();
+        debugger.add_condition(Some(0x206), condition);
+
+        assert_eq!(
+            debugger.run(&mut interpreter, 100),
+            Some(Stop::Condition(0))
+        );
+        assert_eq!(interpreter.program_counter(), 0x206);
+        assert_eq!(interpreter.registers()[1], 3);
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        let condition: Condition = "[i] != 0x10".parse().unwrap();
+        assert_eq!(
+            condition.clauses,
+            vec![(
+                Operand::Memory(Value::I),
+                Comparison::NotEqual,
+                Operand::Number(0x10)
+            )]
+        );
+        assert!("v1 = 2".parse::<Condition>().is_err());
+        assert!("vg == 2".parse::<Condition>().is_err());
+        assert!("[pc] == 2".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn test_fault() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x00, 0xEE]).unwrap();
+        let mut debugger = Debugger::new();
+        assert!(matches!(
+            debugger.run(&mut interpreter, 10),
+            Some(Stop::Fault(Chip8Error::StackUnderflow { .. }))
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::instruction::{decode, Instruction};
+
+/// Decodes `bytes` as if they were loaded at `origin`, returning the address, raw
+/// word and instruction of each opcode. Words that are not instructions decode to
+/// `None`; a trailing odd byte is returned as the high byte of a final word.
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<(u16, u16, Option<Instruction>)> {
+    let mut lines = Vec::with_capacity(bytes.len() / 2 + 1);
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = origin.wrapping_add(offset as u16);
+        let word = read_word(bytes, offset);
+        let mut instruction = decode(word);
+
+        if let Some(Instruction::LdILong(_)) = instruction {
+            if offset + 4 <= bytes.len() {
+                instruction = Some(Instruction::LdILong(read_word(bytes, offset + 2)));
+            } else {
+                instruction = None;
+            }
+        }
+        if offset + 2 > bytes.len() {
+            instruction = None;
+        }
+
+        lines.push((address, word, instruction));
+        offset += instruction.map_or(2, |instruction| instruction.size() as usize);
+    }
+    lines
+}
+
+fn read_word(bytes: &[u8], offset: usize) -> u16 {
+    let high = bytes[offset] as u16;
+    let low = bytes.get(offset + 1).copied().unwrap_or(0) as u16;
+    high << 8 | low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble() {
+        let lines = disassemble(
+            &[0x60, 0x05, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xEE, 0xFF],
+            0x200,
+        );
+        assert_eq!(
+            lines,
+            vec![
+                (0x200, 0x6005, Some(Instruction::LdByte { x: 0, byte: 5 })),
+                (0x202, 0xF000, Some(Instruction::LdILong(0x1234))),
+                (0x206, 0x00EE, Some(Instruction::Ret)),
+                (0x208, 0xFF00, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_data_words() {
+        let lines = disassemble(&[0xFF, 0xFF, 0xF0, 0x00], 0x300);
+        assert_eq!(lines, vec![(0x300, 0xFFFF, None), (0x302, 0xF000, None)]);
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+
+/// A fault raised while loading or running a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    InvalidOpcode { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    MemoryOutOfBounds { address: usize },
+    RomTooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode {opcode:04X} at {pc:04X}")
+            }
+            Chip8Error::StackOverflow { pc } => write!(f, "stack overflow at {pc:04X}"),
+            Chip8Error::StackUnderflow { pc } => write!(f, "stack underflow at {pc:04X}"),
+            Chip8Error::MemoryOutOfBounds { address } => {
+                write!(f, "memory access out of bounds at {address:04X}")
+            }
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(
+                    f,
+                    "ROM is {size} bytes but at most {max} bytes fit in memory"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+/// Why a save state could not be restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    RomMismatch { expected: u64, found: u64 },
+    Truncated,
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::InvalidMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {version}")
+            }
+            StateError::RomMismatch { expected, found } => write!(
+                f,
+                "save state belongs to ROM {found:016X}, but ROM {expected:016X} is loaded"
+            ),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Corrupt => write!(f, "save state is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Why a movie could not be read or played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    RomMismatch { expected: u64, found: u64 },
+    Truncated,
+    Corrupt,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::InvalidMagic => write!(f, "not a movie"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "unsupported movie version {version}")
+            }
+            MovieError::RomMismatch { expected, found } => write!(
+                f,
+                "movie was recorded with ROM {found:016X}, but ROM {expected:016X} is loaded"
+            ),
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::Corrupt => write!(f, "movie is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(err: StateError) -> Self {
+        match err {
+            StateError::Truncated => MovieError::Truncated,
+            _ => MovieError::Corrupt,
+        }
+    }
+}
+
+/// The first frame of a movie after which the machine differed from the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Desync {
+    pub frame: usize,
+    pub expected: u64,
+    pub found: u64,
+}
+
+impl fmt::Display for Desync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "desync at frame {}: state hash {:016X}, recorded {:016X}",
+            self.frame, self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for Desync {}
+
+/// Why a debugger condition could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionError {
+    pub message: String,
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid condition: {}", self.message)
+    }
+}
+
+impl std::error::Error for ConditionError {}
@@ -0,0 +1,361 @@
+use crate::render::RGBA_BYTES;
+
+type Rgba = [u8; RGBA_BYTES];
+
+/// How each display pixel is expanded before the image is scaled up the rest of the
+/// way with plain nearest-neighbour blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    /// Square blocks.
+    #[default]
+    Nearest,
+    /// Scale2x, also known as EPX: each pixel becomes 2x2, rounding off diagonal
+    /// edges.
+    Scale2x,
+    /// The 3x3 version of Scale2x.
+    Scale3x,
+}
+
+/// A pattern darkened over the scaled image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overlay {
+    #[default]
+    None,
+    /// Every other line of the output at half brightness.
+    Scanlines,
+    /// The last line and column of every display pixel at half brightness.
+    Grid,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 3] = [Filter::Nearest, Filter::Scale2x, Filter::Scale3x];
+
+    /// Looks up a filter by name: `nearest`, `scale2x`, `epx` or `scale3x`.
+    pub fn from_name(name: &str) -> Option<Filter> {
+        match name.to_ascii_lowercase().as_str() {
+            "nearest" | "none" => Some(Filter::Nearest),
+            "scale2x" | "epx" => Some(Filter::Scale2x),
+            "scale3x" => Some(Filter::Scale3x),
+            _ => None,
+        }
+    }
+
+    /// The filter after this one, wrapping around.
+    pub fn next(self) -> Filter {
+        let index = Filter::ALL.iter().position(|&filter| filter == self);
+        Filter::ALL[index.map_or(0, |index| index + 1) % Filter::ALL.len()]
+    }
+
+    /// How many output pixels each display pixel becomes, across and down.
+    pub fn factor(self) -> usize {
+        match self {
+            Filter::Nearest => 1,
+            Filter::Scale2x => 2,
+            Filter::Scale3x => 3,
+        }
+    }
+}
+
+impl Overlay {
+    pub const ALL: [Overlay; 3] = [Overlay::None, Overlay::Scanlines, Overlay::Grid];
+
+    /// Looks up an overlay by name: `none`, `scanlines` or `grid`.
+    pub fn from_name(name: &str) -> Option<Overlay> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" => Some(Overlay::None),
+            "scanlines" => Some(Overlay::Scanlines),
+            "grid" => Some(Overlay::Grid),
+            _ => None,
+        }
+    }
+
+    /// The overlay after this one, wrapping around.
+    pub fn next(self) -> Overlay {
+        let index = Overlay::ALL.iter().position(|&overlay| overlay == self);
+        Overlay::ALL[index.map_or(0, |index| index + 1) % Overlay::ALL.len()]
+    }
+}
+
+/// Scales RGBA8 images of the display, as written by `render_rgba` at scale 1, up to
+/// their final size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Upscaler {
+    pub filter: Filter,
+    pub overlay: Overlay,
+}
+
+impl Upscaler {
+    /// Scales the selected rows of `src`, a `width` x `height` image, into `out`, which
+    /// is `scale` times larger each way, and returns the rows it wrote. Filters look
+    /// at neighbouring pixels, so the rows next to the selected ones are rewritten as
+    /// well. The filter falls back to `Nearest` if its factor does not divide `scale`.
+    ///
+    /// Panics if `scale` is 0 or if `src` or `out` is too short.
+    pub fn apply(
+        &self,
+        src: &[u8],
+        width: usize,
+        height: usize,
+        scale: usize,
+        rows: u64,
+        out: &mut [u8],
+    ) -> u64 {
+        assert!(scale > 0, "upscale factor must be at least 1");
+        let stride = width * scale * RGBA_BYTES;
+        assert!(
+            src.len() >= width * height * RGBA_BYTES,
+            "source image too small"
+        );
+        assert!(
+            out.len() >= stride * height * scale,
+            "output image too small"
+        );
+
+        let filter = match self.filter {
+            filter if scale.is_multiple_of(filter.factor()) => filter,
+            _ => Filter::Nearest,
+        };
+        let factor = filter.factor();
+        let block = scale / factor;
+        let visible = u64::MAX >> (64 - height);
+        let rows = match filter {
+            Filter::Nearest => rows & visible,
+            _ => (rows | rows << 1 | rows >> 1) & visible,
+        };
+
+        let image = Image { src, width, height };
+        for row in (0..height).filter(|row| rows & (1 << row) != 0) {
+            let lines = &mut out[row * scale * stride..][..scale * stride];
+            for x in 0..width {
+                let pixels = image.expand(filter, x, row);
+                for (sub_row, sub_pixels) in pixels.chunks_exact(factor).enumerate() {
+                    let line = &mut lines[sub_row * block * stride..][..stride];
+                    let cell = &mut line[x * scale * RGBA_BYTES..][..scale * RGBA_BYTES];
+                    for (pixel, run) in sub_pixels
+                        .iter()
+                        .zip(cell.chunks_exact_mut(block * RGBA_BYTES))
+                    {
+                        for rgba in run.chunks_exact_mut(RGBA_BYTES) {
+                            rgba.copy_from_slice(pixel);
+                        }
+                    }
+                }
+            }
+            // each sub-row is `block` identical lines
+            for sub_row in 0..factor {
+                let (first, rest) =
+                    lines[sub_row * block * stride..][..block * stride].split_at_mut(stride);
+                for line in rest.chunks_exact_mut(stride) {
+                    line.copy_from_slice(first);
+                }
+            }
+            self.darken(lines, stride, scale);
+        }
+        rows
+    }
+
+    /// Applies the overlay to the `scale` lines of one display row.
+    fn darken(&self, lines: &mut [u8], stride: usize, scale: usize) {
+        if scale < 2 {
+            return;
+        }
+        for (y, line) in lines.chunks_exact_mut(stride).enumerate() {
+            for (x, rgba) in line.chunks_exact_mut(RGBA_BYTES).enumerate() {
+                let dim = match self.overlay {
+                    Overlay::None => false,
+                    Overlay::Scanlines => y % 2 == 1,
+                    Overlay::Grid => y == scale - 1 || x % scale == scale - 1,
+                };
+                if dim {
+                    for channel in &mut rgba[..3] {
+                        *channel /= 2;
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Image<'a> {
+    src: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl Image<'_> {
+    /// The pixel at (x + dx, y + dy), repeating the edge pixels outside the image.
+    fn at(&self, x: usize, y: usize, dx: isize, dy: isize) -> Rgba {
+        let x = x.saturating_add_signed(dx).min(self.width - 1);
+        let y = y.saturating_add_signed(dy).min(self.height - 1);
+        let offset = (y * self.width + x) * RGBA_BYTES;
+        self.src[offset..offset + RGBA_BYTES].try_into().unwrap()
+    }
+
+    /// The `factor` x `factor` pixels `filter` turns (x, y) into, row by row.
+    fn expand(&self, filter: Filter, x: usize, y: usize) -> Vec<Rgba> {
+        let p = |dx, dy| self.at(x, y, dx, dy);
+        if filter == Filter::Nearest {
+            return vec![p(0, 0)];
+        }
+        let (a, b, c) = (p(-1, -1), p(0, -1), p(1, -1));
+        let (d, e, f) = (p(-1, 0), p(0, 0), p(1, 0));
+        let (g, h, i) = (p(-1, 1), p(0, 1), p(1, 1));
+        let edge = b != h && d != f;
+        match filter {
+            Filter::Nearest => unreachable!(),
+            Filter::Scale2x if edge => vec![
+                if d == b { d } else { e },
+                if b == f { f } else { e },
+                if d == h { d } else { e },
+                if h == f { f } else { e },
+            ],
+            Filter::Scale2x => vec![e; 4],
+            Filter::Scale3x if edge => vec![
+                if d == b { d } else { e },
+                if (d == b && e != c) || (b == f && e != a) {
+                    b
+                } else {
+                    e
+                },
+                if b == f { f } else { e },
+                if (d == b && e != g) || (d == h && e != a) {
+                    d
+                } else {
+                    e
+                },
+                e,
+                if (b == f && e != i) || (h == f && e != c) {
+                    f
+                } else {
+                    e
+                },
+                if d == h { d } else { e },
+                if (d == h && e != i) || (h == f && e != g) {
+                    h
+                } else {
+                    e
+                },
+                if h == f { f } else { e },
+            ],
+            Filter::Scale3x => vec![e; 9],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X: Rgba = [255, 255, 255, 255];
+    const O: Rgba = [0, 0, 0, 255];
+
+    /// Runs `upscaler` over a pattern given as rows of `X` and `O`.
+    fn upscale(upscaler: Upscaler, pattern: &[&[Rgba]], scale: usize) -> Vec<Vec<Rgba>> {
+        let (width, height) = (pattern[0].len(), pattern.len());
+        let src: Vec<u8> = pattern.iter().flat_map(|row| row.concat()).collect();
+        let mut out = vec![0; width * height * scale * scale * RGBA_BYTES];
+        upscaler.apply(&src, width, height, scale, u64::MAX, &mut out);
+        out.chunks_exact(width * scale * RGBA_BYTES)
+            .map(|line| {
+                line.chunks_exact(RGBA_BYTES)
+                    .map(|rgba| rgba.try_into().unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn filter(filter: Filter) -> Upscaler {
+        Upscaler {
+            filter,
+            overlay: Overlay::None,
+        }
+    }
+
+    #[test]
+    fn test_scale2x_smooths_diagonals() {
+        let out = upscale(filter(Filter::Scale2x), &[&[X, O], &[O, X]], 2);
+        assert_eq!(
+            out,
+            [[X, X, O, O], [X, O, X, O], [O, X, O, X], [O, O, X, X]]
+        );
+    }
+
+    #[test]
+    fn test_scale3x_smooths_diagonals() {
+        let out = upscale(filter(Filter::Scale3x), &[&[X, O], &[O, X]], 3);
+        assert_eq!(
+            out,
+            [
+                [X, X, X, O, O, O],
+                [X, X, O, X, O, O],
+                [X, O, O, X, X, O],
+                [O, X, X, O, O, X],
+                [O, O, X, O, X, X],
+                [O, O, O, X, X, X],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_isolated_pixels_stay_square() {
+        let pattern: &[&[Rgba]] = &[&[O, O, O], &[O, X, O], &[O, O, O]];
+        for upscaler in [filter(Filter::Scale2x), filter(Filter::Scale3x)] {
+            let scale = 6;
+            let out = upscale(upscaler, pattern, scale);
+            let expected: Vec<Vec<Rgba>> = (0..3 * scale)
+                .map(|y| {
+                    (0..3 * scale)
+                        .map(|x| {
+                            if x / scale == 1 && y / scale == 1 {
+                                X
+                            } else {
+                                O
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn test_filter_falls_back_to_nearest() {
+        let pattern: &[&[Rgba]] = &[&[X, O], &[O, X]];
+        assert_eq!(
+            upscale(filter(Filter::Scale3x), pattern, 2),
+            upscale(filter(Filter::Nearest), pattern, 2)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "upscale factor must be at least 1")]
+    fn test_rejects_zero_scale() {
+        upscale(filter(Filter::Nearest), &[&[X]], 0);
+    }
+
+    #[test]
+    fn test_overlays() {
+        const D: Rgba = [127, 127, 127, 255];
+        let scanlines = Upscaler {
+            filter: Filter::Nearest,
+            overlay: Overlay::Scanlines,
+        };
+        assert_eq!(upscale(scanlines, &[&[X]], 2), [[X, X], [D, D]]);
+
+        let grid = Upscaler {
+            filter: Filter::Nearest,
+            overlay: Overlay::Grid,
+        };
+        assert_eq!(upscale(grid, &[&[X]], 3), [[X, X, D], [X, X, D], [D, D, D]]);
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Filter::from_name("EPX"), Some(Filter::Scale2x));
+        assert_eq!(Filter::from_name("scale3x"), Some(Filter::Scale3x));
+        assert_eq!(Filter::from_name("hq2x"), None);
+        assert_eq!(Overlay::from_name("grid"), Some(Overlay::Grid));
+        assert_eq!(Filter::Scale3x.next(), Filter::Nearest);
+    }
+}
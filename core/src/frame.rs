@@ -0,0 +1,26 @@
+use crate::Chip8Error;
+
+/// Instructions per 60 Hz frame unless `Interpreter::set_ticks_per_frame` says
+/// otherwise.
+pub const DEFAULT_TICKS_PER_FRAME: usize = 5;
+
+/// How a frame ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameStatus {
+    Running,
+    /// The program executed `00FD`.
+    Halted,
+    /// Execution stopped on this error; the timers were not decremented.
+    Fault(Chip8Error),
+}
+
+/// What happened during one call to `Interpreter::run_frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSummary {
+    pub instructions: usize,
+    pub screen_changed: bool,
+    /// Rows of the framebuffer that changed, row 0 in bit 0.
+    pub changed_rows: u64,
+    pub sound_active: bool,
+    pub status: FrameStatus,
+}
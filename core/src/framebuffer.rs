@@ -0,0 +1,209 @@
+use crate::{HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH};
+
+/// Number of bitplanes. Pixel values hold one bit per plane, plane 0 in bit 0.
+pub const PLANES: usize = 2;
+const ALL_ROWS: u64 = u64::MAX;
+
+/// The display as bitplanes of 128x64 pixels, one `u128` per row with column 0 in
+/// the most significant bit. Lores mode uses the top-left 64x32.
+///
+/// Every change marks its rows twice: once for the frame summary and once for the
+/// renderer, so a frontend only needs to redraw rows it has not yet taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    planes: [[u128; HIRES_SCREEN_HEIGHT]; PLANES],
+    changed_rows: u64,
+    dirty_rows: u64,
+}
+
+/// The bits of the first `width` columns.
+fn width_mask(width: usize) -> u128 {
+    !0 << (HIRES_SCREEN_WIDTH - width)
+}
+
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        Framebuffer {
+            planes: [[0; HIRES_SCREEN_HEIGHT]; PLANES],
+            changed_rows: ALL_ROWS,
+            dirty_rows: ALL_ROWS,
+        }
+    }
+
+    /// One row of one plane, with column 0 in bit 127.
+    pub fn row(&self, plane: usize, row: usize) -> u128 {
+        self.planes[plane][row]
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let bit = 1 << (HIRES_SCREEN_WIDTH - 1 - x);
+        (0..PLANES)
+            .filter(|&plane| self.planes[plane][y] & bit != 0)
+            .fold(0, |pixel, plane| pixel | 1 << plane)
+    }
+
+    /// The pixel values of the first `width` columns of `row`.
+    pub fn row_pixels(&self, row: usize, width: usize) -> impl Iterator<Item = u8> + '_ {
+        (0..width).map(move |x| self.pixel(x, row))
+    }
+
+    /// Rows changed since the interpreter last finished a frame, row 0 in bit 0.
+    pub fn changed_rows(&self) -> u64 {
+        self.changed_rows
+    }
+
+    pub(crate) fn take_changed_rows(&mut self) -> u64 {
+        std::mem::take(&mut self.changed_rows)
+    }
+
+    /// Rows changed since the renderer last took them, row 0 in bit 0.
+    pub fn dirty_rows(&self) -> u64 {
+        self.dirty_rows
+    }
+
+    pub(crate) fn take_dirty_rows(&mut self) -> u64 {
+        std::mem::take(&mut self.dirty_rows)
+    }
+
+    fn mark(&mut self, rows: u64) {
+        self.changed_rows |= rows;
+        self.dirty_rows |= rows;
+    }
+
+    pub(crate) fn mark_all_changed(&mut self) {
+        self.mark(ALL_ROWS);
+    }
+
+    pub(crate) fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
+        let bit = 1 << (HIRES_SCREEN_WIDTH - 1 - x);
+        for (plane, rows) in self.planes.iter_mut().enumerate() {
+            if value & (1 << plane) != 0 {
+                rows[y] |= bit;
+            } else {
+                rows[y] &= !bit;
+            }
+        }
+        self.mark(1 << y);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.planes = [[0; HIRES_SCREEN_HEIGHT]; PLANES];
+        self.mark_all_changed();
+    }
+
+    /// Clears the planes selected in the `planes` bitmask.
+    pub(crate) fn clear_planes(&mut self, planes: u8) {
+        for plane in selected(planes) {
+            self.planes[plane] = [0; HIRES_SCREEN_HEIGHT];
+        }
+        self.mark_all_changed();
+    }
+
+    /// XORs `bits` into a row and reports whether any lit pixel was turned off.
+    pub(crate) fn xor_row(&mut self, plane: usize, row: usize, bits: u128) -> bool {
+        let pixels = &mut self.planes[plane][row];
+        let collision = *pixels & bits != 0;
+        *pixels ^= bits;
+        if bits != 0 {
+            self.mark(1 << row);
+        }
+        collision
+    }
+
+    /// Moves the selected planes within the visible `width` x `height` area, filling
+    /// the vacated pixels with 0.
+    pub(crate) fn scroll(&mut self, planes: u8, dx: isize, dy: isize, width: usize, height: usize) {
+        let mask = width_mask(width);
+        for plane in selected(planes) {
+            let previous = self.planes[plane];
+            for row in 0..height {
+                let source = row as isize - dy;
+                let pixels = if (0..height as isize).contains(&source) {
+                    previous[source as usize]
+                } else {
+                    0
+                };
+                let moved = if dx >= 0 { pixels >> dx } else { pixels << -dx };
+                self.planes[plane][row] = moved & mask;
+            }
+        }
+        self.mark(ALL_ROWS >> (64 - height));
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn selected(planes: u8) -> impl Iterator<Item = usize> {
+    (0..PLANES).filter(move |&plane| planes & (1 << plane) != 0)
+}
+
+/// Places one sprite row, given as 16 bits with its leftmost pixel in bit 15, at
+/// column `x` of a screen `width` pixels wide. Pixels past the right edge are
+/// dropped when `clip` is set and wrap around to the left otherwise.
+pub(crate) fn sprite_row(bits: u16, x: usize, width: usize, clip: bool) -> u128 {
+    let aligned = (bits as u128) << (HIRES_SCREEN_WIDTH - 16);
+    let shifted = aligned >> x;
+    let mask = width_mask(width);
+    if clip {
+        return shifted & mask;
+    }
+    let overflow = if width == HIRES_SCREEN_WIDTH {
+        aligned
+            .checked_shl((HIRES_SCREEN_WIDTH - x) as u32)
+            .unwrap_or(0)
+    } else {
+        (shifted & !mask) << width
+    };
+    (shifted & mask) | overflow
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sprite_row_wraps_or_clips() {
+        let column = |x: usize| 1u128 << (127 - x);
+        // 0xF000: four pixels from column 62
+        assert_eq!(
+            sprite_row(0xF000, 62, 64, false),
+            column(62) | column(63) | column(0) | column(1)
+        );
+        assert_eq!(sprite_row(0xF000, 62, 64, true), column(62) | column(63));
+        assert_eq!(
+            sprite_row(0xF000, 126, 128, false),
+            column(126) | column(127) | column(0) | column(1)
+        );
+        assert_eq!(sprite_row(0x8000, 0, 128, false), column(0));
+    }
+
+    #[test]
+    fn test_xor_row_reports_collisions_and_changes() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.take_changed_rows();
+        assert!(!framebuffer.xor_row(0, 3, 0b1100));
+        assert!(framebuffer.xor_row(0, 3, 0b0100));
+        assert!(!framebuffer.xor_row(1, 3, 0b0100));
+        assert_eq!(framebuffer.row(0, 3), 0b1000);
+        assert_eq!(framebuffer.pixel(125, 3), 0b10);
+        assert_eq!(framebuffer.pixel(124, 3), 0b01);
+        assert_eq!(framebuffer.take_changed_rows(), 1 << 3);
+        assert_eq!(framebuffer.take_dirty_rows(), u64::MAX);
+    }
+
+    #[test]
+    fn test_scroll_masks_to_visible_area() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.set_pixel(62, 0, 1);
+        framebuffer.set_pixel(1, 31, 0b11);
+        framebuffer.scroll(0b01, 4, 0, 64, 32);
+        assert_eq!(framebuffer.pixel(62, 0), 0);
+        assert_eq!(framebuffer.pixel(66, 0), 0);
+        assert_eq!(framebuffer.pixel(5, 31), 0b01);
+        assert_eq!(framebuffer.pixel(1, 31), 0b10);
+    }
+}
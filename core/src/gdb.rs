@@ -0,0 +1,435 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+
+use crate::debugger::{Access, Debugger, Stop};
+use crate::{Chip8Error, FrameStatus, Interpreter, NUMBER_OF_REGISTERS, STACK_SIZE};
+
+/// Registers in the order of the `g` packet: V0-VF, I, PC, SP, DT and ST. 16-bit
+/// registers are sent big-endian, like the rest of the machine.
+const REGISTER_SIZES: [usize; NUMBER_OF_REGISTERS + 5] = [
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 1, 1, 1,
+];
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+enum Input {
+    Packet(String),
+    Interrupt,
+}
+
+/// A GDB remote serial protocol server on a loopback port.
+///
+/// Nothing blocks: call `poll` once per frame. While a debugger is attached the
+/// server owns execution, so a frontend should only run the interpreter itself when
+/// `is_connected` is false.
+pub struct GdbServer {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    input: Vec<u8>,
+    running: bool,
+}
+
+impl GdbServer {
+    pub fn bind(port: u16) -> io::Result<GdbServer> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+        Ok(GdbServer {
+            listener,
+            client: None,
+            input: Vec::new(),
+            running: false,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Whether the attached debugger has let the target run.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Accepts a connection, answers pending packets and, after `c`, runs one frame.
+    pub fn poll(
+        &mut self,
+        debugger: &mut Debugger,
+        interpreter: &mut Interpreter,
+    ) -> io::Result<()> {
+        if self.client.is_none() && !self.accept()? {
+            return Ok(());
+        }
+
+        if !self.read_input()? {
+            self.disconnect();
+            return Ok(());
+        }
+
+        while let Some(input) = self.next_input()? {
+            match input {
+                Input::Interrupt => {
+                    if self.running {
+                        self.running = false;
+                        self.send("S02")?;
+                    }
+                }
+                Input::Packet(packet) => {
+                    let detach = packet == "D";
+                    if let Some(reply) = self.handle(&packet, debugger, interpreter) {
+                        self.send(&reply)?;
+                    }
+                    if detach || packet == "k" {
+                        self.disconnect();
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
+        if self.running {
+            let stop = match debugger.run_frame(interpreter) {
+                Ok(summary) => match summary.status {
+                    FrameStatus::Running => None,
+                    FrameStatus::Halted => Some(Stop::Halted),
+                    FrameStatus::Fault(err) => Some(Stop::Fault(err)),
+                },
+                Err(stop) => Some(stop),
+            };
+            if let Some(stop) = stop {
+                self.running = false;
+                self.send(&stop_reply(stop))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn accept(&mut self) -> io::Result<bool> {
+        match self.listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(true)?;
+                stream.set_nodelay(true)?;
+                self.client = Some(stream);
+                self.input.clear();
+                // gdb expects the target to be stopped when it attaches
+                self.running = false;
+                Ok(true)
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.client = None;
+        self.input.clear();
+        self.running = false;
+    }
+
+    /// Returns `false` once the client has gone away.
+    fn read_input(&mut self) -> io::Result<bool> {
+        let Some(client) = self.client.as_mut() else {
+            return Ok(false);
+        };
+        let mut buffer = [0; 4096];
+        loop {
+            match client.read(&mut buffer) {
+                Ok(0) => return Ok(false),
+                Ok(len) => self.input.extend_from_slice(&buffer[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(true),
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn next_input(&mut self) -> io::Result<Option<Input>> {
+        loop {
+            match self.input.first() {
+                None => return Ok(None),
+                Some(b'+') | Some(b'-') => {
+                    self.input.remove(0);
+                }
+                Some(0x03) => {
+                    self.input.remove(0);
+                    return Ok(Some(Input::Interrupt));
+                }
+                Some(b'$') => {
+                    let Some(end) = self.input.iter().position(|&byte| byte == b'#') else {
+                        return Ok(None);
+                    };
+                    if self.input.len() < end + 3 {
+                        return Ok(None);
+                    }
+                    let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+                    let data = &packet[1..end];
+                    let checksum = std::str::from_utf8(&packet[end + 1..])
+                        .ok()
+                        .and_then(|text| u8::from_str_radix(text, 16).ok());
+                    if checksum != Some(checksum_of(data)) {
+                        self.write_raw(b"-")?;
+                        continue;
+                    }
+                    self.write_raw(b"+")?;
+                    return Ok(Some(Input::Packet(
+                        String::from_utf8_lossy(data).into_owned(),
+                    )));
+                }
+                Some(_) => {
+                    self.input.remove(0);
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum_of(data.as_bytes()));
+        self.write_raw(packet.as_bytes())
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let Some(client) = self.client.as_mut() else {
+            return Ok(());
+        };
+        let mut written = 0;
+        while written < bytes.len() {
+            match client.write(&bytes[written..]) {
+                Ok(len) => written += len,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => std::thread::yield_now(),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the reply to a packet, or `None` when it is sent later.
+    fn handle(
+        &mut self,
+        packet: &str,
+        debugger: &mut Debugger,
+        interpreter: &mut Interpreter,
+    ) -> Option<String> {
+        let Some(command) = packet.chars().next() else {
+            return Some(String::new());
+        };
+        let (_, args) = packet.split_at(command.len_utf8());
+
+        let reply = match command {
+            '?' => "S05".to_string(),
+            'g' => to_hex(&read_registers(interpreter)),
+            'G' => ok_or_error(
+                from_hex(args).is_some_and(|bytes| write_registers(interpreter, &bytes)),
+            ),
+            'p' => usize::from_str_radix(args, 16)
+                .ok()
+                .and_then(|register| register_bytes(interpreter, register))
+                .map_or_else(|| "E01".to_string(), |bytes| to_hex(&bytes)),
+            'm' => parse_range(args)
+                .and_then(|(address, len)| {
+                    interpreter.memory.get(address..address.checked_add(len)?)
+                })
+                .map_or_else(|| "E01".to_string(), to_hex),
+            'M' => ok_or_error(write_memory(interpreter, args).is_some()),
+            'c' | 's' => {
+                if let Ok(address) = u16::from_str_radix(args, 16) {
+                    interpreter.program_counter = address;
+                }
+                if command == 'c' {
+                    self.running = true;
+                    return None;
+                }
+                stop_reply(debugger.step(interpreter))
+            }
+            'Z' | 'z' => match parse_watch(args) {
+                Some((kind, address, len)) => {
+                    ok_or_error(update_watch(debugger, command == 'Z', kind, address, len))
+                }
+                None => String::new(),
+            },
+            'q' => query(args),
+            'H' => "OK".to_string(),
+            'D' => "OK".to_string(),
+            'k' => return None,
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+}
+
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        return "PacketSize=1000;qXfer:features:read+".to_string();
+    }
+    if args == "Attached" {
+        return "1".to_string();
+    }
+    if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        let Some((offset, len)) = parse_range(range) else {
+            return "E01".to_string();
+        };
+        let xml = TARGET_XML.as_bytes();
+        let start = offset.min(xml.len());
+        let end = start.saturating_add(len).min(xml.len());
+        let marker = if end == xml.len() { 'l' } else { 'm' };
+        return format!("{marker}{}", String::from_utf8_lossy(&xml[start..end]));
+    }
+    String::new()
+}
+
+fn stop_reply(stop: Stop) -> String {
+    match stop {
+        Stop::Watchpoint { address, access } => {
+            let kind = match access {
+                Access::Read => "rwatch",
+                Access::Write => "watch",
+            };
+            format!("T05{kind}:{address:x};")
+        }
+        Stop::Fault(Chip8Error::InvalidOpcode { .. }) => "S04".to_string(),
+        Stop::Fault(_) => "S0b".to_string(),
+        Stop::Halted => "W00".to_string(),
+        _ => "S05".to_string(),
+    }
+}
+
+fn ok_or_error(ok: bool) -> String {
+    if ok { "OK" } else { "E01" }.to_string()
+}
+
+fn read_registers(interpreter: &Interpreter) -> Vec<u8> {
+    (0..REGISTER_SIZES.len())
+        .flat_map(|register| register_bytes(interpreter, register).unwrap())
+        .collect()
+}
+
+fn register_bytes(interpreter: &Interpreter, register: usize) -> Option<Vec<u8>> {
+    let bytes = match register {
+        0..=15 => vec![interpreter.registers[register]],
+        16 => interpreter.index_register.to_be_bytes().to_vec(),
+        17 => interpreter.program_counter.to_be_bytes().to_vec(),
+        18 => vec![interpreter.stack_pointer],
+        19 => vec![interpreter.delay_timer],
+        20 => vec![interpreter.sound_timer],
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+fn write_registers(interpreter: &mut Interpreter, bytes: &[u8]) -> bool {
+    if bytes.len() != REGISTER_SIZES.iter().sum::<usize>() || bytes[20] as usize > STACK_SIZE {
+        return false;
+    }
+    interpreter.registers.copy_from_slice(&bytes[..16]);
+    interpreter.index_register = u16::from_be_bytes([bytes[16], bytes[17]]);
+    interpreter.program_counter = u16::from_be_bytes([bytes[18], bytes[19]]);
+    interpreter.stack_pointer = bytes[20];
+    interpreter.delay_timer = bytes[21];
+    interpreter.sound_timer = bytes[22];
+    true
+}
+
+/// `M addr,len:data`
+fn write_memory(interpreter: &mut Interpreter, args: &str) -> Option<()> {
+    let (range, data) = args.split_once(':')?;
+    let (address, len) = parse_range(range)?;
+    let bytes = from_hex(data).filter(|bytes| bytes.len() == len)?;
+    interpreter
+        .memory
+        .get_mut(address..address.checked_add(len)?)?
+        .copy_from_slice(&bytes);
+    Some(())
+}
+
+/// `type,addr,kind` of a `Z` or `z` packet.
+fn parse_watch(args: &str) -> Option<(u8, u16, u16)> {
+    let mut parts = args.split(',');
+    let kind = parts.next()?.parse().ok()?;
+    let address = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let len = u16::from_str_radix(parts.next()?.split(';').next()?, 16).ok()?;
+    Some((kind, address, len))
+}
+
+fn update_watch(debugger: &mut Debugger, insert: bool, kind: u8, address: u16, len: u16) -> bool {
+    let accesses: &[Access] = match kind {
+        0 | 1 => {
+            if insert {
+                debugger.add_breakpoint(address);
+            } else {
+                debugger.remove_breakpoint(address);
+            }
+            return true;
+        }
+        2 => &[Access::Write],
+        3 => &[Access::Read],
+        4 => &[Access::Read, Access::Write],
+        _ => return false,
+    };
+    for offset in 0..len.max(1) {
+        let address = address.wrapping_add(offset);
+        for &access in accesses {
+            if insert {
+                debugger.add_watchpoint(address, access);
+            } else {
+                debugger.remove_watchpoint(address, access);
+            }
+        }
+    }
+    true
+}
+
+/// `addr,len` in hex.
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let (address, len) = args.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
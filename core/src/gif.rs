@@ -0,0 +1,301 @@
+use crate::{Interpreter, Palette, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH};
+use std::collections::HashMap;
+
+/// Colour table entries; one per pixel value.
+const COLORS: usize = 4;
+/// LZW starts with codes one bit wider than a pixel index.
+const MIN_CODE_SIZE: u8 = 2;
+const MAX_CODES: u16 = 0x1000;
+const MAX_SUB_BLOCK: usize = 0xFF;
+/// Most viewers stretch shorter delays to 10 centiseconds, so no frame is kept for
+/// less than this.
+const MIN_DELAY: u64 = 2;
+const FRAME_RATE: u64 = 60;
+
+/// Records consecutive 60 Hz frames of the display as a looping animated GIF.
+///
+/// Frames are always hires-sized; lores pixels are drawn 2x2. A frame identical to
+/// the one before extends it, and a frame that would be shown for less than two
+/// centiseconds is replaced by the next one, so the total length stays exact.
+#[derive(Debug, Clone)]
+pub struct GifRecorder {
+    scale: usize,
+    out: Vec<u8>,
+    /// The frame waiting to be written and the tick it started on.
+    pending: Option<(Vec<u8>, u64)>,
+    ticks: u64,
+}
+
+impl GifRecorder {
+    /// Starts a recording in `palette`, `scale` image pixels per hires pixel.
+    ///
+    /// Panics if `scale` is 0.
+    pub fn new(palette: &Palette, scale: usize) -> GifRecorder {
+        assert!(scale > 0, "GIF scale must be at least 1");
+        let mut out = b"GIF89a".to_vec();
+        out.extend(((HIRES_SCREEN_WIDTH * scale) as u16).to_le_bytes());
+        out.extend(((HIRES_SCREEN_HEIGHT * scale) as u16).to_le_bytes());
+        // global colour table of 2^(1 + 1) entries, 2 bits per primary
+        out.extend([0x80 | 0x10 | 0x01, 0, 0]);
+        for color in &palette.colors[..COLORS] {
+            out.extend(color);
+        }
+        // loop forever
+        out.extend([0x21, 0xFF, 0x0B]);
+        out.extend(b"NETSCAPE2.0");
+        out.extend([0x03, 0x01, 0x00, 0x00, 0x00]);
+
+        GifRecorder {
+            scale,
+            out,
+            pending: None,
+            ticks: 0,
+        }
+    }
+
+    /// Adds the current display as the next frame.
+    pub fn record(&mut self, interpreter: &Interpreter) {
+        let pixels = self.pixels(interpreter);
+        let tick = self.ticks;
+        self.ticks += 1;
+        match self.pending.as_mut() {
+            Some((pending, _)) if *pending == pixels => {}
+            Some((pending, start)) if delay(*start, tick) < MIN_DELAY => *pending = pixels,
+            _ => {
+                if let Some((pending, start)) = self.pending.take() {
+                    self.write_frame(&pending, delay(start, tick));
+                }
+                self.pending = Some((pixels, tick));
+            }
+        }
+    }
+
+    /// Number of frames recorded so far.
+    pub fn frames(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Ends the recording and returns the GIF file.
+    pub fn finish(mut self) -> Vec<u8> {
+        if let Some((pending, start)) = self.pending.take() {
+            self.write_frame(&pending, delay(start, self.ticks).max(MIN_DELAY));
+        }
+        self.out.push(0x3B);
+        self.out
+    }
+
+    /// The colour indices of the scaled frame, top row first.
+    fn pixels(&self, interpreter: &Interpreter) -> Vec<u8> {
+        let framebuffer = interpreter.framebuffer();
+        let zoom = HIRES_SCREEN_WIDTH / interpreter.screen_width();
+        let (width, height) = (
+            HIRES_SCREEN_WIDTH * self.scale,
+            HIRES_SCREEN_HEIGHT * self.scale,
+        );
+        let pixel_size = zoom * self.scale;
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| framebuffer.pixel(x / pixel_size, y / pixel_size))
+            .collect()
+    }
+
+    fn write_frame(&mut self, pixels: &[u8], delay: u64) {
+        // graphic control extension: no disposal, no transparency
+        self.out.extend([0x21, 0xF9, 0x04, 0x00]);
+        self.out.extend((delay as u16).to_le_bytes());
+        self.out.extend([0x00, 0x00]);
+
+        // image descriptor covering the whole screen, no local colour table
+        self.out.push(0x2C);
+        self.out.extend([0, 0, 0, 0]);
+        self.out
+            .extend(((HIRES_SCREEN_WIDTH * self.scale) as u16).to_le_bytes());
+        self.out
+            .extend(((HIRES_SCREEN_HEIGHT * self.scale) as u16).to_le_bytes());
+        self.out.push(0);
+
+        self.out.push(MIN_CODE_SIZE);
+        for block in lzw(pixels).chunks(MAX_SUB_BLOCK) {
+            self.out.push(block.len() as u8);
+            self.out.extend(block);
+        }
+        self.out.push(0);
+    }
+}
+
+/// Centiseconds between two 60 Hz ticks, rounded so they add up over a recording.
+fn delay(start: u64, end: u64) -> u64 {
+    let centiseconds = |tick: u64| (tick * 100 + FRAME_RATE / 2) / FRAME_RATE;
+    centiseconds(end) - centiseconds(start)
+}
+
+/// Packs codes least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    len: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.bits |= (code as u32) << self.len;
+        self.len += size;
+        while self.len >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+/// GIF's variable-width LZW, restarting the table with a clear code once it is full.
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    let clear = 1 << MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut writer = BitWriter::default();
+    let mut size = MIN_CODE_SIZE + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+
+    writer.write(clear, size);
+    let mut indices = indices.iter();
+    if let Some(&first) = indices.next() {
+        let mut prefix = first as u16;
+        for &index in indices {
+            if let Some(&code) = table.get(&(prefix, index)) {
+                prefix = code;
+                continue;
+            }
+            writer.write(prefix, size);
+            if next < MAX_CODES {
+                if next == 1 << size {
+                    size += 1;
+                }
+                table.insert((prefix, index), next);
+                next += 1;
+            } else {
+                writer.write(clear, size);
+                table.clear();
+                size = MIN_CODE_SIZE + 1;
+                next = end + 1;
+            }
+            prefix = index as u16;
+        }
+        writer.write(prefix, size);
+    }
+    writer.write(end, size);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes `lzw` output following the GIF specification.
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let clear = 1usize << MIN_CODE_SIZE;
+        let initial: Vec<Vec<u8>> = (0..clear + 2).map(|code| vec![code as u8]).collect();
+        let mut table = initial.clone();
+        let mut size = MIN_CODE_SIZE + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        let (mut bits, mut len, mut bytes) = (0u32, 0u8, data.iter());
+        loop {
+            while len < size {
+                bits |= (*bytes.next().unwrap() as u32) << len;
+                len += 8;
+            }
+            let code = (bits & ((1 << size) - 1)) as usize;
+            bits >>= size;
+            len -= size;
+
+            if code == clear {
+                table = initial.clone();
+                size = MIN_CODE_SIZE + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return out;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.clone(), vec![previous[0]]].concat(),
+                (None, None) => panic!("undefined code {code}"),
+            };
+            out.extend(&entry);
+            if let Some(previous) = previous {
+                table.push([previous, vec![entry[0]]].concat());
+            }
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        let empty: &[u8] = &[];
+        let runs = [0u8; 5000];
+        // enough distinct strings to fill the table and clear it
+        let noise: Vec<u8> = (0..40_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8 & 3)
+            .collect();
+        for indices in [empty, &[1, 2, 3, 0], &runs, &noise] {
+            assert_eq!(unlzw(&lzw(indices)), indices);
+        }
+    }
+
+    /// The delays of the frames in a GIF, in centiseconds.
+    fn delays(gif: &[u8]) -> Vec<u16> {
+        (0..gif.len() - 6)
+            .filter(|&offset| gif[offset..offset + 3] == [0x21, 0xF9, 0x04])
+            .map(|offset| u16::from_le_bytes([gif[offset + 4], gif[offset + 5]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_frames_last_sixtieths() {
+        let mut interpreter = Interpreter::new();
+        // draw the font sprite for 0, then erase it
+        interpreter.load(&[0xD0, 0x05, 0xD0, 0x05]).unwrap();
+        let mut recorder = GifRecorder::new(&Palette::CLASSIC, 1);
+        for frame in 0..18 {
+            if frame == 6 || frame == 12 {
+                interpreter.tick().unwrap();
+            }
+            recorder.record(&interpreter);
+        }
+        assert_eq!(recorder.frames(), 18);
+        let gif = recorder.finish();
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(gif[6..10], [128, 0, 64, 0]);
+        assert_eq!(gif.last(), Some(&0x3B));
+        assert_eq!(delays(&gif), [10, 10, 10]);
+    }
+
+    #[test]
+    fn test_short_frames_are_merged() {
+        let mut interpreter = Interpreter::new();
+        // flicker the font sprite for 0 every frame
+        interpreter.load(&[0xD0, 0x05, 0x12, 0x00]).unwrap();
+        let mut recorder = GifRecorder::new(&Palette::CLASSIC, 1);
+        for _ in 0..30 {
+            interpreter.tick().unwrap();
+            interpreter.tick().unwrap();
+            recorder.record(&interpreter);
+        }
+        let delays = delays(&recorder.finish());
+        assert!(delays.iter().all(|&delay| delay >= MIN_DELAY as u16));
+        assert_eq!(delays.iter().sum::<u16>(), 50);
+    }
+}
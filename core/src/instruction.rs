@@ -0,0 +1,432 @@
+use std::fmt;
+
+/// A decoded opcode, covering CHIP-8, SUPER-CHIP and XO-CHIP.
+///
+/// `x` and `y` are register numbers, `n` is a nibble, `byte` an immediate byte and
+/// `addr` a 12-bit address. Decoding does not depend on the interpreter mode, so the
+/// XO-CHIP instructions decode even though a classic machine rejects them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0NNN, machine code routine. Only 0000 is run, as a no-op.
+    Sys(u16),
+    /// 00CN
+    ScrollDown(u8),
+    /// 00DN, XO-CHIP
+    ScrollUp(u8),
+    /// 00E0
+    Cls,
+    /// 00EE
+    Ret,
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FD
+    Exit,
+    /// 00FE
+    Lores,
+    /// 00FF
+    Hires,
+    /// 1NNN
+    Jp(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN
+    SeByte { x: u8, byte: u8 },
+    /// 4XNN
+    SneByte { x: u8, byte: u8 },
+    /// 5XY0
+    SeReg { x: u8, y: u8 },
+    /// 5XY2, XO-CHIP
+    SaveRange { x: u8, y: u8 },
+    /// 5XY3, XO-CHIP
+    LoadRange { x: u8, y: u8 },
+    /// 6XNN
+    LdByte { x: u8, byte: u8 },
+    /// 7XNN
+    AddByte { x: u8, byte: u8 },
+    /// 8XY0
+    LdReg { x: u8, y: u8 },
+    /// 8XY1
+    Or { x: u8, y: u8 },
+    /// 8XY2
+    And { x: u8, y: u8 },
+    /// 8XY3
+    Xor { x: u8, y: u8 },
+    /// 8XY4
+    AddReg { x: u8, y: u8 },
+    /// 8XY5
+    Sub { x: u8, y: u8 },
+    /// 8XY6
+    Shr { x: u8, y: u8 },
+    /// 8XY7
+    Subn { x: u8, y: u8 },
+    /// 8XYE
+    Shl { x: u8, y: u8 },
+    /// 9XY0
+    SneReg { x: u8, y: u8 },
+    /// ANNN
+    LdI(u16),
+    /// BNNN
+    JpV0(u16),
+    /// CXNN
+    Rnd { x: u8, byte: u8 },
+    /// DXYN
+    Drw { x: u8, y: u8, n: u8 },
+    /// EX9E
+    Skp(u8),
+    /// EXA1
+    Sknp(u8),
+    /// F000 NNNN, XO-CHIP. The address lives in the word after the opcode, so
+    /// `decode` returns `LdILong(0)`.
+    LdILong(u16),
+    /// FN01, XO-CHIP
+    Plane(u8),
+    /// F002, XO-CHIP
+    Audio,
+    /// FX07
+    LdVxDt(u8),
+    /// FX0A
+    LdVxK(u8),
+    /// FX15
+    LdDtVx(u8),
+    /// FX18
+    LdStVx(u8),
+    /// FX1E
+    AddI(u8),
+    /// FX29
+    LdF(u8),
+    /// FX30
+    LdHf(u8),
+    /// FX33
+    LdB(u8),
+    /// FX3A, XO-CHIP
+    Pitch(u8),
+    /// FX55
+    LdMem(u8),
+    /// FX65
+    LdRegs(u8),
+    /// FX75
+    SaveFlags(u8),
+    /// FX85
+    LoadFlags(u8),
+}
+
+/// Decodes a single opcode, or `None` if it is not an instruction.
+pub fn decode(opcode: u16) -> Option<Instruction> {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let byte = (opcode & 0x00FF) as u8;
+    let addr = opcode & 0x0FFF;
+
+    let instruction = match (opcode >> 12, x, y, n) {
+        (0, 0, 0xC, n) => Instruction::ScrollDown(n),
+        (0, 0, 0xD, n) => Instruction::ScrollUp(n),
+        (0, 0, 0xE, 0) => Instruction::Cls,
+        (0, 0, 0xE, 0xE) => Instruction::Ret,
+        (0, 0, 0xF, 0xB) => Instruction::ScrollRight,
+        (0, 0, 0xF, 0xC) => Instruction::ScrollLeft,
+        (0, 0, 0xF, 0xD) => Instruction::Exit,
+        (0, 0, 0xF, 0xE) => Instruction::Lores,
+        (0, 0, 0xF, 0xF) => Instruction::Hires,
+        (0, _, _, _) => Instruction::Sys(addr),
+        (1, _, _, _) => Instruction::Jp(addr),
+        (2, _, _, _) => Instruction::Call(addr),
+        (3, _, _, _) => Instruction::SeByte { x, byte },
+        (4, _, _, _) => Instruction::SneByte { x, byte },
+        (5, _, _, 0) => Instruction::SeReg { x, y },
+        (5, _, _, 2) => Instruction::SaveRange { x, y },
+        (5, _, _, 3) => Instruction::LoadRange { x, y },
+        (6, _, _, _) => Instruction::LdByte { x, byte },
+        (7, _, _, _) => Instruction::AddByte { x, byte },
+        (8, _, _, 0) => Instruction::LdReg { x, y },
+        (8, _, _, 1) => Instruction::Or { x, y },
+        (8, _, _, 2) => Instruction::And { x, y },
+        (8, _, _, 3) => Instruction::Xor { x, y },
+        (8, _, _, 4) => Instruction::AddReg { x, y },
+        (8, _, _, 5) => Instruction::Sub { x, y },
+        (8, _, _, 6) => Instruction::Shr { x, y },
+        (8, _, _, 7) => Instruction::Subn { x, y },
+        (8, _, _, 0xE) => Instruction::Shl { x, y },
+        (9, _, _, 0) => Instruction::SneReg { x, y },
+        (0xA, _, _, _) => Instruction::LdI(addr),
+        (0xB, _, _, _) => Instruction::JpV0(addr),
+        (0xC, _, _, _) => Instruction::Rnd { x, byte },
+        (0xD, _, _, _) => Instruction::Drw { x, y, n },
+        (0xE, _, 9, 0xE) => Instruction::Skp(x),
+        (0xE, _, 0xA, 1) => Instruction::Sknp(x),
+        (0xF, 0, 0, 0) => Instruction::LdILong(0),
+        (0xF, _, 0, 1) => Instruction::Plane(x),
+        (0xF, 0, 0, 2) => Instruction::Audio,
+        (0xF, _, 0, 7) => Instruction::LdVxDt(x),
+        (0xF, _, 0, 0xA) => Instruction::LdVxK(x),
+        (0xF, _, 1, 5) => Instruction::LdDtVx(x),
+        (0xF, _, 1, 8) => Instruction::LdStVx(x),
+        (0xF, _, 1, 0xE) => Instruction::AddI(x),
+        (0xF, _, 2, 9) => Instruction::LdF(x),
+        (0xF, _, 3, 0) => Instruction::LdHf(x),
+        (0xF, _, 3, 3) => Instruction::LdB(x),
+        (0xF, _, 3, 0xA) => Instruction::Pitch(x),
+        (0xF, _, 5, 5) => Instruction::LdMem(x),
+        (0xF, _, 6, 5) => Instruction::LdRegs(x),
+        (0xF, _, 7, 5) => Instruction::SaveFlags(x),
+        (0xF, _, 8, 5) => Instruction::LoadFlags(x),
+        _ => return None,
+    };
+    Some(instruction)
+}
+
+impl Instruction {
+    /// The first word of the instruction. `LdILong` is followed by its address.
+    pub fn encode(&self) -> u16 {
+        let xy = |base: u16, x: u8, y: u8, n: u16| {
+            base | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | n
+        };
+        let xb = |base: u16, x: u8, byte: u8| base | (x as u16 & 0xF) << 8 | byte as u16;
+        let fx = |x: u8, low: u16| 0xF000 | (x as u16 & 0xF) << 8 | low;
+
+        match *self {
+            Instruction::Sys(addr) => addr & 0x0FFF,
+            Instruction::ScrollDown(n) => 0x00C0 | (n as u16 & 0xF),
+            Instruction::ScrollUp(n) => 0x00D0 | (n as u16 & 0xF),
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Lores => 0x00FE,
+            Instruction::Hires => 0x00FF,
+            Instruction::Jp(addr) => 0x1000 | (addr & 0x0FFF),
+            Instruction::Call(addr) => 0x2000 | (addr & 0x0FFF),
+            Instruction::SeByte { x, byte } => xb(0x3000, x, byte),
+            Instruction::SneByte { x, byte } => xb(0x4000, x, byte),
+            Instruction::SeReg { x, y } => xy(0x5000, x, y, 0),
+            Instruction::SaveRange { x, y } => xy(0x5000, x, y, 2),
+            Instruction::LoadRange { x, y } => xy(0x5000, x, y, 3),
+            Instruction::LdByte { x, byte } => xb(0x6000, x, byte),
+            Instruction::AddByte { x, byte } => xb(0x7000, x, byte),
+            Instruction::LdReg { x, y } => xy(0x8000, x, y, 0),
+            Instruction::Or { x, y } => xy(0x8000, x, y, 1),
+            Instruction::And { x, y } => xy(0x8000, x, y, 2),
+            Instruction::Xor { x, y } => xy(0x8000, x, y, 3),
+            Instruction::AddReg { x, y } => xy(0x8000, x, y, 4),
+            Instruction::Sub { x, y } => xy(0x8000, x, y, 5),
+            Instruction::Shr { x, y } => xy(0x8000, x, y, 6),
+            Instruction::Subn { x, y } => xy(0x8000, x, y, 7),
+            Instruction::Shl { x, y } => xy(0x8000, x, y, 0xE),
+            Instruction::SneReg { x, y } => xy(0x9000, x, y, 0),
+            Instruction::LdI(addr) => 0xA000 | (addr & 0x0FFF),
+            Instruction::JpV0(addr) => 0xB000 | (addr & 0x0FFF),
+            Instruction::Rnd { x, byte } => xb(0xC000, x, byte),
+            Instruction::Drw { x, y, n } => xy(0xD000, x, y, n as u16 & 0xF),
+            Instruction::Skp(x) => xb(0xE000, x, 0x9E),
+            Instruction::Sknp(x) => xb(0xE000, x, 0xA1),
+            Instruction::LdILong(_) => 0xF000,
+            Instruction::Plane(n) => fx(n, 0x01),
+            Instruction::Audio => 0xF002,
+            Instruction::LdVxDt(x) => fx(x, 0x07),
+            Instruction::LdVxK(x) => fx(x, 0x0A),
+            Instruction::LdDtVx(x) => fx(x, 0x15),
+            Instruction::LdStVx(x) => fx(x, 0x18),
+            Instruction::AddI(x) => fx(x, 0x1E),
+            Instruction::LdF(x) => fx(x, 0x29),
+            Instruction::LdHf(x) => fx(x, 0x30),
+            Instruction::LdB(x) => fx(x, 0x33),
+            Instruction::Pitch(x) => fx(x, 0x3A),
+            Instruction::LdMem(x) => fx(x, 0x55),
+            Instruction::LdRegs(x) => fx(x, 0x65),
+            Instruction::SaveFlags(x) => fx(x, 0x75),
+            Instruction::LoadFlags(x) => fx(x, 0x85),
+        }
+    }
+
+    /// Size in bytes, including the address word of `LdILong`.
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LdILong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// The instruction in Octo syntax, e.g. `v1 += 0x02`.
+    pub fn to_octo(&self) -> String {
+        match *self {
+            Instruction::Sys(addr) => format!("0x{:02X} 0x{:02X}", addr >> 8, addr & 0xFF),
+            Instruction::ScrollDown(n) => format!("scroll-down {n}"),
+            Instruction::ScrollUp(n) => format!("scroll-up {n}"),
+            Instruction::Cls => "clear".to_string(),
+            Instruction::Ret => "return".to_string(),
+            Instruction::ScrollRight => "scroll-right".to_string(),
+            Instruction::ScrollLeft => "scroll-left".to_string(),
+            Instruction::Exit => "exit".to_string(),
+            Instruction::Lores => "lores".to_string(),
+            Instruction::Hires => "hires".to_string(),
+            Instruction::Jp(addr) => format!("jump 0x{addr:03X}"),
+            Instruction::Call(addr) => format!(":call 0x{addr:03X}"),
+            // the skip instructions read as the condition under which the next one runs
+            Instruction::SeByte { x, byte } => format!("if v{x:x} != 0x{byte:02X} then"),
+            Instruction::SneByte { x, byte } => format!("if v{x:x} == 0x{byte:02X} then"),
+            Instruction::SeReg { x, y } => format!("if v{x:x} != v{y:x} then"),
+            Instruction::SaveRange { x, y } => format!("save v{x:x} - v{y:x}"),
+            Instruction::LoadRange { x, y } => format!("load v{x:x} - v{y:x}"),
+            Instruction::LdByte { x, byte } => format!("v{x:x} := 0x{byte:02X}"),
+            Instruction::AddByte { x, byte } => format!("v{x:x} += 0x{byte:02X}"),
+            Instruction::LdReg { x, y } => format!("v{x:x} := v{y:x}"),
+            Instruction::Or { x, y } => format!("v{x:x} |= v{y:x}"),
+            Instruction::And { x, y } => format!("v{x:x} &= v{y:x}"),
+            Instruction::Xor { x, y } => format!("v{x:x} ^= v{y:x}"),
+            Instruction::AddReg { x, y } => format!("v{x:x} += v{y:x}"),
+            Instruction::Sub { x, y } => format!("v{x:x} -= v{y:x}"),
+            Instruction::Shr { x, y } => format!("v{x:x} >>= v{y:x}"),
+            Instruction::Subn { x, y } => format!("v{x:x} =- v{y:x}"),
+            Instruction::Shl { x, y } => format!("v{x:x} <<= v{y:x}"),
+            Instruction::SneReg { x, y } => format!("if v{x:x} == v{y:x} then"),
+            Instruction::LdI(addr) => format!("i := 0x{addr:03X}"),
+            Instruction::JpV0(addr) => format!("jump0 0x{addr:03X}"),
+            Instruction::Rnd { x, byte } => format!("v{x:x} := random 0x{byte:02X}"),
+            Instruction::Drw { x, y, n } => format!("sprite v{x:x} v{y:x} {n}"),
+            Instruction::Skp(x) => format!("if v{x:x} -key then"),
+            Instruction::Sknp(x) => format!("if v{x:x} key then"),
+            Instruction::LdILong(addr) => format!("i := long 0x{addr:04X}"),
+            Instruction::Plane(n) => format!("plane {n}"),
+            Instruction::Audio => "audio".to_string(),
+            Instruction::LdVxDt(x) => format!("v{x:x} := delay"),
+            Instruction::LdVxK(x) => format!("v{x:x} := key"),
+            Instruction::LdDtVx(x) => format!("delay := v{x:x}"),
+            Instruction::LdStVx(x) => format!("buzzer := v{x:x}"),
+            Instruction::AddI(x) => format!("i += v{x:x}"),
+            Instruction::LdF(x) => format!("i := hex v{x:x}"),
+            Instruction::LdHf(x) => format!("i := bighex v{x:x}"),
+            Instruction::LdB(x) => format!("bcd v{x:x}"),
+            Instruction::Pitch(x) => format!("pitch := v{x:x}"),
+            Instruction::LdMem(x) => format!("save v{x:x}"),
+            Instruction::LdRegs(x) => format!("load v{x:x}"),
+            Instruction::SaveFlags(x) => format!("saveflags v{x:x}"),
+            Instruction::LoadFlags(x) => format!("loadflags v{x:x}"),
+        }
+    }
+}
+
+/// Cowgod's mnemonics, e.g. `ADD V1, 0x02`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Sys(addr) => write!(f, "SYS 0x{addr:03X}"),
+            Instruction::ScrollDown(n) => write!(f, "SCD {n}"),
+            Instruction::ScrollUp(n) => write!(f, "SCU {n}"),
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Lores => write!(f, "LOW"),
+            Instruction::Hires => write!(f, "HIGH"),
+            Instruction::Jp(addr) => write!(f, "JP 0x{addr:03X}"),
+            Instruction::Call(addr) => write!(f, "CALL 0x{addr:03X}"),
+            Instruction::SeByte { x, byte } => write!(f, "SE V{x:X}, 0x{byte:02X}"),
+            Instruction::SneByte { x, byte } => write!(f, "SNE V{x:X}, 0x{byte:02X}"),
+            Instruction::SeReg { x, y } => write!(f, "SE V{x:X}, V{y:X}"),
+            Instruction::SaveRange { x, y } => write!(f, "LD [I], V{x:X} - V{y:X}"),
+            Instruction::LoadRange { x, y } => write!(f, "LD V{x:X} - V{y:X}, [I]"),
+            Instruction::LdByte { x, byte } => write!(f, "LD V{x:X}, 0x{byte:02X}"),
+            Instruction::AddByte { x, byte } => write!(f, "ADD V{x:X}, 0x{byte:02X}"),
+            Instruction::LdReg { x, y } => write!(f, "LD V{x:X}, V{y:X}"),
+            Instruction::Or { x, y } => write!(f, "OR V{x:X}, V{y:X}"),
+            Instruction::And { x, y } => write!(f, "AND V{x:X}, V{y:X}"),
+            Instruction::Xor { x, y } => write!(f, "XOR V{x:X}, V{y:X}"),
+            Instruction::AddReg { x, y } => write!(f, "ADD V{x:X}, V{y:X}"),
+            Instruction::Sub { x, y } => write!(f, "SUB V{x:X}, V{y:X}"),
+            Instruction::Shr { x, y } => write!(f, "SHR V{x:X}, V{y:X}"),
+            Instruction::Subn { x, y } => write!(f, "SUBN V{x:X}, V{y:X}"),
+            Instruction::Shl { x, y } => write!(f, "SHL V{x:X}, V{y:X}"),
+            Instruction::SneReg { x, y } => write!(f, "SNE V{x:X}, V{y:X}"),
+            Instruction::LdI(addr) => write!(f, "LD I, 0x{addr:03X}"),
+            Instruction::JpV0(addr) => write!(f, "JP V0, 0x{addr:03X}"),
+            Instruction::Rnd { x, byte } => write!(f, "RND V{x:X}, 0x{byte:02X}"),
+            Instruction::Drw { x, y, n } => write!(f, "DRW V{x:X}, V{y:X}, {n}"),
+            Instruction::Skp(x) => write!(f, "SKP V{x:X}"),
+            Instruction::Sknp(x) => write!(f, "SKNP V{x:X}"),
+            Instruction::LdILong(addr) => write!(f, "LD I, LONG 0x{addr:04X}"),
+            Instruction::Plane(n) => write!(f, "PLANE {n}"),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::LdVxDt(x) => write!(f, "LD V{x:X}, DT"),
+            Instruction::LdVxK(x) => write!(f, "LD V{x:X}, K"),
+            Instruction::LdDtVx(x) => write!(f, "LD DT, V{x:X}"),
+            Instruction::LdStVx(x) => write!(f, "LD ST, V{x:X}"),
+            Instruction::AddI(x) => write!(f, "ADD I, V{x:X}"),
+            Instruction::LdF(x) => write!(f, "LD F, V{x:X}"),
+            Instruction::LdHf(x) => write!(f, "LD HF, V{x:X}"),
+            Instruction::LdB(x) => write!(f, "LD B, V{x:X}"),
+            Instruction::Pitch(x) => write!(f, "LD PITCH, V{x:X}"),
+            Instruction::LdMem(x) => write!(f, "LD [I], V{x:X}"),
+            Instruction::LdRegs(x) => write!(f, "LD V{x:X}, [I]"),
+            Instruction::SaveFlags(x) => write!(f, "LD R, V{x:X}"),
+            Instruction::LoadFlags(x) => write!(f, "LD V{x:X}, R"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(0x00E0), Some(Instruction::Cls));
+        assert_eq!(decode(0x0000), Some(Instruction::Sys(0)));
+        assert_eq!(
+            decode(0x3155),
+            Some(Instruction::SeByte { x: 1, byte: 0x55 })
+        );
+        assert_eq!(decode(0x8AB4), Some(Instruction::AddReg { x: 0xA, y: 0xB }));
+        assert_eq!(decode(0xD125), Some(Instruction::Drw { x: 1, y: 2, n: 5 }));
+        assert_eq!(decode(0xF000), Some(Instruction::LdILong(0)));
+        assert_eq!(decode(0xF201), Some(Instruction::Plane(2)));
+        assert_eq!(decode(0xF002), Some(Instruction::Audio));
+        assert_eq!(decode(0xF102), None);
+        assert_eq!(decode(0xF43A), Some(Instruction::Pitch(4)));
+        assert_eq!(decode(0x5121), None);
+        assert_eq!(decode(0x8128), None);
+        assert_eq!(decode(0x9121), None);
+        assert_eq!(decode(0xE100), None);
+        assert_eq!(decode(0xF1FF), None);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for opcode in 0..=u16::MAX {
+            if let Some(instruction) = decode(opcode) {
+                assert_eq!(instruction.encode(), opcode, "{instruction:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let text = |opcode| decode(opcode).unwrap().to_string();
+        assert_eq!(text(0x00EE), "RET");
+        assert_eq!(text(0x1228), "JP 0x228");
+        assert_eq!(text(0x6A02), "LD VA, 0x02");
+        assert_eq!(text(0x8126), "SHR V1, V2");
+        assert_eq!(text(0xD01F), "DRW V0, V1, 15");
+        assert_eq!(text(0xF533), "LD B, V5");
+        assert_eq!(text(0x5123), "LD V1 - V2, [I]");
+        assert_eq!(text(0xF23A), "LD PITCH, V2");
+    }
+
+    #[test]
+    fn test_octo() {
+        let octo = |opcode| decode(opcode).unwrap().to_octo();
+        assert_eq!(octo(0x00E0), "clear");
+        assert_eq!(octo(0x2300), ":call 0x300");
+        assert_eq!(octo(0x3A00), "if va != 0x00 then");
+        assert_eq!(octo(0x8127), "v1 =- v2");
+        assert_eq!(octo(0xC3FF), "v3 := random 0xFF");
+        assert_eq!(octo(0xE19E), "if v1 -key then");
+        assert_eq!(octo(0xF429), "i := hex v4");
+        assert_eq!(octo(0xF002), "audio");
+        assert_eq!(octo(0xF53A), "pitch := v5");
+        assert_eq!(Instruction::LdILong(0x1234).to_octo(), "i := long 0x1234");
+    }
+}
@@ -1,12 +1,62 @@
-use rand::Rng;
+mod audio;
+pub mod debugger;
+pub mod disassembler;
+mod error;
+mod filter;
+mod frame;
+mod framebuffer;
+pub mod gdb;
+mod gif;
+mod hash;
+mod instruction;
+mod movie;
+mod palette;
+mod phosphor;
+mod png;
+mod quirks;
+mod random;
+mod render;
+mod rewind;
+mod state;
+mod timing;
+mod trace;
+
+pub use audio::DEFAULT_FREQUENCY;
+pub use error::{Chip8Error, ConditionError, Desync, MovieError, StateError};
+pub use filter::{Filter, Overlay, Upscaler};
+pub use frame::{FrameStatus, FrameSummary, DEFAULT_TICKS_PER_FRAME};
+pub use framebuffer::{Framebuffer, PLANES};
+pub use gif::GifRecorder;
+pub use hash::fnv1a;
+pub use instruction::{decode, Instruction};
+pub use movie::{Movie, MovieFrame, MoviePlayer, MOVIE_VERSION};
+pub use palette::{Palette, Rgb};
+pub use phosphor::{Phosphor, DEFAULT_DECAY_FRAMES, FULL_INTENSITY};
+pub use png::encode_png;
+pub use quirks::{IndexIncrement, Quirks};
+pub use random::{RandomSource, SplitMix64, DEFAULT_SEED};
+pub use render::{rgba_len, RGBA_BYTES};
+pub use rewind::Rewind;
+pub use state::STATE_VERSION;
+pub use timing::Timing;
+pub use trace::{TraceEntry, TraceSink, TraceWriter};
+
+use audio::{Voice, PATTERN_SIZE};
+use framebuffer::sprite_row;
+use timing::{vip_cycles, vip_skip_cycles, VIP_CYCLES_PER_FRAME};
+use trace::Tracer;
 
 const PROGRAM_START: u16 = 0x200;
 const STACK_SIZE: usize = 16;
 const NUMBER_OF_REGISTERS: usize = 16;
 const MEMORY_SIZE: usize = 4096;
+const XO_MEMORY_SIZE: usize = 65536;
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
+pub const HIRES_SCREEN_WIDTH: usize = 128;
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
 const NUMBER_OF_KEYS: usize = 16;
+const NUMBER_OF_FLAGS: usize = 16;
 const FONTSET_SIZE: usize = 80;
 const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -26,29 +76,47 @@ const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+const BIG_FONTSET_START: usize = FONTSET_SIZE;
+const BIG_FONTSET_SIZE: usize = 160;
+const BIG_FONTSET: [u8; BIG_FONTSET_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
 
 trait Memory {
-    fn mem_read(&self, address: u16) -> u8;
+    fn mem_read(&self, address: u16) -> Result<u8, Chip8Error>;
 
-    fn mem_read_16(&self, address: u16) -> u16 {
-        let hi = self.mem_read(address) as u16;
-        let lo = self.mem_read(address + 1) as u16;
-        hi << 8 | lo
+    fn mem_read_16(&self, address: u16) -> Result<u16, Chip8Error> {
+        let hi = self.mem_read(address)? as u16;
+        let next = address
+            .checked_add(1)
+            .ok_or(Chip8Error::MemoryOutOfBounds {
+                address: address as usize + 1,
+            })?;
+        let lo = self.mem_read(next)? as u16;
+        Ok(hi << 8 | lo)
     }
 
-    fn mem_write(&mut self, address: u16, data: u8);
-
-    fn mem_write_16(&mut self, address: u16, data: u16) {
-        let hi = (data >> 8) as u8;
-        let lo = (data & 0xff) as u8;
-        self.mem_write(address, hi);
-        self.mem_write(address + 1, lo);
-    }
+    fn mem_write(&mut self, address: u16, data: u8) -> Result<(), Chip8Error>;
 }
 
 trait Stack {
-    fn stack_push(&mut self, address: u16);
-    fn stack_pop(&mut self) -> u16;
+    fn stack_push(&mut self, address: u16) -> Result<(), Chip8Error>;
+    fn stack_pop(&mut self) -> Result<u16, Chip8Error>;
 }
 
 #[derive(Debug, Clone)]
@@ -58,250 +126,387 @@ pub struct Interpreter {
     stack_pointer: u8,
     stack: [u16; STACK_SIZE],
     registers: [u8; NUMBER_OF_REGISTERS],
-    memory: [u8; MEMORY_SIZE],
+    memory: Vec<u8>,
     delay_timer: u8,
     sound_timer: u8,
-    screen: [[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
+    framebuffer: Framebuffer,
+    planes: u8,
+    hires: bool,
+    xo_chip: bool,
+    halted: bool,
     keys: [bool; NUMBER_OF_KEYS],
+    flags: [u8; NUMBER_OF_FLAGS],
+    quirks: Quirks,
+    seed: u64,
+    rng: Box<dyn RandomSource>,
+    rom_hash: u64,
+    voice: Voice,
+    tracer: Tracer,
+    ticks_per_frame: usize,
+    timing: Timing,
+    /// VIP cycles left in this frame; negative once an instruction overran it.
+    cycles_left: i32,
+    waiting_for_vblank: bool,
 }
 
 impl Memory for Interpreter {
-    fn mem_read(&self, address: u16) -> u8 {
-        self.memory[address as usize]
-    }
-
-    fn mem_write(&mut self, address: u16, data: u8) {
-        self.memory[address as usize] = data
+    fn mem_read(&self, address: u16) -> Result<u8, Chip8Error> {
+        self.memory
+            .get(address as usize)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds {
+                address: address as usize,
+            })
+    }
+
+    fn mem_write(&mut self, address: u16, data: u8) -> Result<(), Chip8Error> {
+        let cell = self
+            .memory
+            .get_mut(address as usize)
+            .ok_or(Chip8Error::MemoryOutOfBounds {
+                address: address as usize,
+            })?;
+        *cell = data;
+        Ok(())
     }
 }
 
 impl Stack for Interpreter {
-    fn stack_push(&mut self, address: u16) {
+    fn stack_push(&mut self, address: u16) -> Result<(), Chip8Error> {
+        if self.stack_pointer as usize == STACK_SIZE {
+            return Err(Chip8Error::StackOverflow {
+                pc: self.program_counter.wrapping_sub(2),
+            });
+        }
         self.stack[self.stack_pointer as usize] = address;
         self.stack_pointer += 1;
+        Ok(())
     }
 
-    fn stack_pop(&mut self) -> u16 {
+    fn stack_pop(&mut self) -> Result<u16, Chip8Error> {
+        if self.stack_pointer == 0 {
+            return Err(Chip8Error::StackUnderflow {
+                pc: self.program_counter.wrapping_sub(2),
+            });
+        }
         self.stack_pointer -= 1;
-        self.stack[self.stack_pointer as usize]
+        Ok(self.stack[self.stack_pointer as usize])
     }
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
+        Self::with_quirks(Quirks::default())
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> Interpreter {
         let mut interpreter = Self {
             program_counter: 0,
             index_register: 0,
             stack_pointer: 0,
             stack: [0; STACK_SIZE],
             registers: [0; NUMBER_OF_REGISTERS],
-            memory: [0; MEMORY_SIZE],
+            memory: vec![0; MEMORY_SIZE],
             delay_timer: 0,
             sound_timer: 0,
-            screen: [[false; SCREEN_WIDTH]; SCREEN_HEIGHT],
+            framebuffer: Framebuffer::new(),
+            planes: 1,
+            hires: false,
+            xo_chip: false,
+            halted: false,
             keys: [false; NUMBER_OF_KEYS],
+            flags: [0; NUMBER_OF_FLAGS],
+            quirks,
+            seed: DEFAULT_SEED,
+            rng: Box::new(SplitMix64::new(DEFAULT_SEED)),
+            rom_hash: 0,
+            voice: Voice::default(),
+            tracer: Tracer::default(),
+            ticks_per_frame: DEFAULT_TICKS_PER_FRAME,
+            timing: Timing::default(),
+            cycles_left: VIP_CYCLES_PER_FRAME,
+            waiting_for_vblank: false,
         };
 
-        interpreter.memory[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        interpreter.load_fonts();
         interpreter
     }
 
+    fn load_fonts(&mut self) {
+        self.memory[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        self.memory[BIG_FONTSET_START..BIG_FONTSET_START + BIG_FONTSET_SIZE]
+            .copy_from_slice(&BIG_FONTSET);
+    }
+
     fn cls(&mut self) {
-        self.screen = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
+        self.framebuffer.clear_planes(self.planes);
     }
 
-    fn ret(&mut self) {
-        self.program_counter = self.stack_pop();
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.screen_width(), self.screen_height());
+        self.framebuffer.scroll(self.planes, dx, dy, width, height);
     }
 
-    fn jp_to_addr(&mut self, opcode: u16) {
-        self.program_counter = opcode & 0x0FFF;
+    fn scroll_down(&mut self, n: u8) {
+        self.scroll(0, n as isize);
     }
 
-    fn call_at_addr(&mut self, opcode: u16) {
-        self.stack_push(self.program_counter);
-        self.program_counter = opcode & 0x0FFF;
+    fn scroll_up(&mut self, n: u8) {
+        self.scroll(0, -(n as isize));
     }
 
-    fn se_vx_and_byte(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let vx = self.registers[x as usize];
-        if vx == (opcode & 0x00FF) as u8 {
-            self.program_counter += 2;
+    fn scroll_right(&mut self) {
+        self.scroll(4, 0);
+    }
+
+    fn scroll_left(&mut self) {
+        self.scroll(-4, 0);
+    }
+
+    fn exit(&mut self) {
+        self.halted = true;
+    }
+
+    fn lores(&mut self) {
+        self.hires = false;
+        self.framebuffer.clear();
+    }
+
+    fn hires(&mut self) {
+        self.hires = true;
+        self.framebuffer.clear();
+    }
+
+    fn skip_next_instruction(&mut self) {
+        // F000 NNNN is twice as long as every other instruction
+        if self.xo_chip && self.mem_read_16(self.program_counter) == Ok(0xF000) {
+            self.program_counter = self.program_counter.wrapping_add(4);
+        } else {
+            self.program_counter = self.program_counter.wrapping_add(2);
         }
     }
 
-    fn sne_vx_and_byte(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let vx = self.registers[x as usize];
-        if vx != (opcode & 0x00FF) as u8 {
-            self.program_counter += 2;
+    fn ret(&mut self) -> Result<(), Chip8Error> {
+        self.program_counter = self.stack_pop()?;
+        Ok(())
+    }
+
+    fn jp_to_addr(&mut self, addr: u16) {
+        self.program_counter = addr;
+    }
+
+    fn call_at_addr(&mut self, addr: u16) -> Result<(), Chip8Error> {
+        self.stack_push(self.program_counter)?;
+        self.program_counter = addr;
+        Ok(())
+    }
+
+    fn se_vx_and_byte(&mut self, x: u8, byte: u8) {
+        if self.registers[x as usize] == byte {
+            self.skip_next_instruction();
         }
     }
 
-    fn se_vx_and_vy(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
-        let vx = self.registers[x as usize];
-        let vy = self.registers[y as usize];
-        if vx == vy {
-            self.program_counter += 2;
+    fn sne_vx_and_byte(&mut self, x: u8, byte: u8) {
+        if self.registers[x as usize] != byte {
+            self.skip_next_instruction();
         }
     }
 
-    fn ld_vx_with_byte(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        self.registers[x as usize] = (opcode & 0x00FF) as u8;
+    fn se_vx_and_vy(&mut self, x: u8, y: u8) {
+        if self.registers[x as usize] == self.registers[y as usize] {
+            self.skip_next_instruction();
+        }
     }
 
-    fn add_vx_with_byte(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        self.registers[x as usize] =
-            self.registers[x as usize].wrapping_add((opcode & 0x00FF) as u8);
+    fn ld_mem_with_register_range(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        for (offset, register) in register_range(x, y).enumerate() {
+            let address = self.index_register.wrapping_add(offset as u16);
+            self.mem_write(address, self.registers[register as usize])?;
+        }
+        Ok(())
     }
 
-    fn ld_vx_with_vy(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
+    fn ld_register_range_with_mem(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        for (offset, register) in register_range(x, y).enumerate() {
+            let address = self.index_register.wrapping_add(offset as u16);
+            self.registers[register as usize] = self.mem_read(address)?;
+        }
+        Ok(())
+    }
+
+    fn ld_vx_with_byte(&mut self, x: u8, byte: u8) {
+        self.registers[x as usize] = byte;
+    }
+
+    fn add_vx_with_byte(&mut self, x: u8, byte: u8) {
+        self.registers[x as usize] = self.registers[x as usize].wrapping_add(byte);
+    }
+
+    fn ld_vx_with_vy(&mut self, x: u8, y: u8) {
         self.registers[x as usize] = self.registers[y as usize];
     }
 
-    fn or_vx_with_vy(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
+    fn or_vx_with_vy(&mut self, x: u8, y: u8) {
         let result = self.registers[x as usize] | self.registers[y as usize];
         self.registers[x as usize] = result;
+        if self.quirks.logic_resets_vf {
+            self.registers[0xF] = 0;
+        }
     }
 
-    fn and_vx_with_vy(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
+    fn and_vx_with_vy(&mut self, x: u8, y: u8) {
         let result = self.registers[x as usize] & self.registers[y as usize];
         self.registers[x as usize] = result;
+        if self.quirks.logic_resets_vf {
+            self.registers[0xF] = 0;
+        }
     }
 
-    fn xor_vx_with_vy(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
+    fn xor_vx_with_vy(&mut self, x: u8, y: u8) {
         let result = self.registers[x as usize] ^ self.registers[y as usize];
         self.registers[x as usize] = result;
+        if self.quirks.logic_resets_vf {
+            self.registers[0xF] = 0;
+        }
     }
 
-    fn add_vx_with_vy(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
+    /// The ALU handlers write VF after the result, so the flag wins when X is F.
+    fn add_vx_with_vy(&mut self, x: u8, y: u8) {
         let (result, carry) =
             self.registers[x as usize].overflowing_add(self.registers[y as usize]);
-        self.registers[0xF] = if carry { 1 } else { 0 };
         self.registers[x as usize] = result;
+        self.registers[0xF] = if carry { 1 } else { 0 };
     }
 
-    fn sub_vx_with_vy(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
+    fn sub_vx_with_vy(&mut self, x: u8, y: u8) {
         let (result, borrow) =
             self.registers[x as usize].overflowing_sub(self.registers[y as usize]);
-        self.registers[0xF] = if borrow { 0 } else { 1 };
         self.registers[x as usize] = result;
+        self.registers[0xF] = if borrow { 0 } else { 1 };
     }
 
-    fn shr_vx(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn shr_vx(&mut self, x: u8, y: u8) {
+        self.load_shift_source(x, y);
         let vx = self.registers[x as usize];
+        self.registers[x as usize] = vx >> 1;
         self.registers[0xF] = vx & 1;
-        self.registers[x as usize] >>= 1;
     }
 
-    fn subn_vx_with_vy(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
+    fn load_shift_source(&mut self, x: u8, y: u8) {
+        if self.quirks.shift_uses_vy {
+            self.registers[x as usize] = self.registers[y as usize];
+        }
+    }
+
+    fn subn_vx_with_vy(&mut self, x: u8, y: u8) {
         let (result, borrow) =
             self.registers[y as usize].overflowing_sub(self.registers[x as usize]);
-        self.registers[0xF] = if borrow { 0 } else { 1 };
         self.registers[x as usize] = result;
+        self.registers[0xF] = if borrow { 0 } else { 1 };
     }
 
-    fn shl_vx(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn shl_vx(&mut self, x: u8, y: u8) {
+        self.load_shift_source(x, y);
         let vx = self.registers[x as usize];
-        self.registers[0xF] = (vx >> 7) & 1;
-        self.registers[x as usize] <<= 1;
+        self.registers[x as usize] = vx << 1;
+        self.registers[0xF] = vx >> 7;
     }
 
-    fn sne_vx_and_vy(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
-        let vx = self.registers[x as usize];
-        let vy = self.registers[y as usize];
-        if vx != vy {
-            self.program_counter += 2;
+    fn sne_vx_and_vy(&mut self, x: u8, y: u8) {
+        if self.registers[x as usize] != self.registers[y as usize] {
+            self.skip_next_instruction();
         }
     }
 
-    fn ld_i_with_addr(&mut self, opcode: u16) {
-        self.index_register = opcode & 0x0FFF;
+    fn ld_i_with_addr(&mut self, addr: u16) {
+        self.index_register = addr;
+    }
+
+    fn ld_i_with_long_addr(&mut self) -> Result<(), Chip8Error> {
+        self.index_register = self.mem_read_16(self.program_counter)?;
+        self.program_counter = self.program_counter.wrapping_add(2);
+        Ok(())
     }
 
-    fn jp_to_v0_plus_addr(&mut self, opcode: u16) {
-        self.program_counter = (opcode & 0x0FFF) + self.registers[0] as u16;
+    fn select_planes(&mut self, n: u8) {
+        self.planes = n & 0b11;
+    }
+
+    fn jp_to_v0_plus_addr(&mut self, addr: u16) {
+        let offset = if self.quirks.jump_uses_vx {
+            let x = (addr & 0x0F00) >> 8;
+            self.registers[x as usize]
+        } else {
+            self.registers[0]
+        };
+        self.program_counter = addr + offset as u16;
     }
 
-    fn rnd(&mut self, opcode: u16) {
-        let mut rng = rand::thread_rng();
-        let byte = rng.gen_range(0..=255) as u8;
-        let x = (opcode & 0x0F00) >> 8;
-        self.registers[x as usize] = byte & (opcode & 0x00FF) as u8;
+    fn rnd(&mut self, x: u8, byte: u8) {
+        self.registers[x as usize] = self.rng.next_byte() & byte;
     }
 
-    fn draw(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        let y = (opcode & 0x00F0) >> 4;
-        let n = (opcode & 0x000F) as u8; // height
+    fn draw(&mut self, x: u8, y: u8, n: u8) -> Result<(), Chip8Error> {
+        // a height of 0 draws a 16x16 sprite
+        let (sprite_width, sprite_height) = if n == 0 { (16, 16) } else { (8, n) };
 
-        let x_coordinate = self.registers[x as usize];
-        let y_coordinate = self.registers[y as usize];
+        let width = self.screen_width();
+        let height = self.screen_height();
+        let x_coordinate = self.registers[x as usize] as usize % width;
+        let y_coordinate = self.registers[y as usize] as usize % height;
         let mut is_flipped = false;
+        let mut address = self.index_register;
 
-        for dy in 0..n {
-            let addr = self.index_register + dy as u16;
-            let pixels = self.mem_read(addr);
-            for dx in 0..8 {
-                if (pixels & (0b1000_0000 >> dx)) != 0 {
-                    let col = (x_coordinate + dx) as usize % SCREEN_WIDTH;
-                    let row = (y_coordinate + dy) as usize % SCREEN_HEIGHT;
+        for plane in 0..PLANES {
+            if self.planes & (1 << plane) == 0 {
+                continue;
+            }
 
-                    is_flipped |= self.screen[row][col];
-                    self.screen[row][col] ^= true;
+            for dy in 0..sprite_height {
+                let pixels = if sprite_width == 16 {
+                    let pixels = self.mem_read_16(address)?;
+                    address = address.wrapping_add(2);
+                    pixels
+                } else {
+                    let pixels = (self.mem_read(address)? as u16) << 8;
+                    address = address.wrapping_add(1);
+                    pixels
+                };
+                let row = y_coordinate + dy as usize;
+                if self.quirks.clip_sprites && row >= height {
+                    continue;
                 }
+                let bits = sprite_row(pixels, x_coordinate, width, self.quirks.clip_sprites);
+                is_flipped |= self.framebuffer.xor_row(plane, row % height, bits);
             }
         }
 
         self.registers[0xF] = if is_flipped { 1 } else { 0 };
+        self.waiting_for_vblank = self.quirks.display_wait;
+        Ok(())
     }
 
-    fn skp(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    /// Only the low nibble of VX selects the key, as on the VIP.
+    fn skp(&mut self, x: u8) {
         let vx = self.registers[x as usize];
-        if self.keys[vx as usize] {
-            self.program_counter += 2
+        if self.keys[(vx & 0xF) as usize] {
+            self.skip_next_instruction()
         }
     }
 
-    fn sknp(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn sknp(&mut self, x: u8) {
         let vx = self.registers[x as usize];
-        if !self.keys[vx as usize] {
-            self.program_counter += 2
+        if !self.keys[(vx & 0xF) as usize] {
+            self.skip_next_instruction()
         }
     }
 
-    fn ld_vx_with_dt(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn ld_vx_with_dt(&mut self, x: u8) {
         self.registers[x as usize] = self.delay_timer;
     }
 
-    fn ld_vx_with_key_press(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn ld_vx_with_key_press(&mut self, x: u8) {
         let mut pressed = false;
         for i in 0..self.keys.len() {
             if self.keys[i] {
@@ -312,57 +517,95 @@ impl Interpreter {
         }
 
         if !pressed {
-            self.program_counter -= 2
+            self.program_counter = self.program_counter.wrapping_sub(2);
         }
     }
 
-    fn ld_dt_with_vx(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn ld_dt_with_vx(&mut self, x: u8) {
         self.delay_timer = self.registers[x as usize];
     }
 
-    fn ld_st_with_vx(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn ld_st_with_vx(&mut self, x: u8) {
         self.sound_timer = self.registers[x as usize];
     }
 
-    fn add_i_with_vx(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn add_i_with_vx(&mut self, x: u8) {
         self.index_register = self
             .index_register
             .wrapping_add(self.registers[x as usize] as u16);
     }
 
-    fn ld_i_with_font_address(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn ld_i_with_font_address(&mut self, x: u8) {
         let data = self.registers[x as usize] as u16;
         self.index_register = data * 5
     }
 
-    fn ld_bcd(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn ld_i_with_big_font_address(&mut self, x: u8) {
+        let data = self.registers[x as usize] as u16;
+        self.index_register = BIG_FONTSET_START as u16 + data * 10
+    }
+
+    fn ld_bcd(&mut self, x: u8) -> Result<(), Chip8Error> {
         let vx = self.registers[x as usize] as f32;
 
         let hundreds = (vx / 100.0).floor() as u8;
         let tens = ((vx / 10.0) % 10.0).floor() as u8;
         let ones = (vx % 10.0).floor() as u8;
 
-        self.mem_write(self.index_register, hundreds);
-        self.mem_write(self.index_register + 1, tens);
-        self.mem_write(self.index_register + 2, ones);
+        self.mem_write(self.index_register, hundreds)?;
+        self.mem_write(self.index_register.wrapping_add(1), tens)?;
+        self.mem_write(self.index_register.wrapping_add(2), ones)
+    }
+
+    fn ld_mem_with_registers(&mut self, x: u8) -> Result<(), Chip8Error> {
+        for i in 0..=x as u16 {
+            self.mem_write(
+                self.index_register.wrapping_add(i),
+                self.registers[i as usize],
+            )?;
+        }
+        self.increment_index_after_load_store(x as u16);
+        Ok(())
     }
 
-    fn ld_mem_with_registers(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        for i in 0..=x {
-            self.mem_write(self.index_register + i, self.registers[i as usize]);
+    fn ld_registers_with_mem(&mut self, x: u8) -> Result<(), Chip8Error> {
+        for i in 0..=x as u16 {
+            self.registers[i as usize] = self.mem_read(self.index_register.wrapping_add(i))?;
         }
+        self.increment_index_after_load_store(x as u16);
+        Ok(())
+    }
+
+    fn ld_flags_with_registers(&mut self, x: u8) {
+        let x = x as usize;
+        self.flags[..=x].copy_from_slice(&self.registers[..=x]);
     }
 
-    fn ld_registers_with_mem(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
-        for i in 0..=x {
-            self.registers[i as usize] = self.mem_read(self.index_register + i);
+    fn ld_registers_with_flags(&mut self, x: u8) {
+        let x = x as usize;
+        self.registers[..=x].copy_from_slice(&self.flags[..=x]);
+    }
+
+    fn ld_audio_pattern(&mut self) -> Result<(), Chip8Error> {
+        let mut pattern = [0; PATTERN_SIZE];
+        for (offset, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.mem_read(self.index_register.wrapping_add(offset as u16))?;
+        }
+        self.voice.pattern = Some(pattern);
+        Ok(())
+    }
+
+    fn ld_pitch_with_vx(&mut self, x: u8) {
+        self.voice.pitch = self.registers[x as usize];
+    }
+
+    fn increment_index_after_load_store(&mut self, x: u16) {
+        match self.quirks.index_increment {
+            IndexIncrement::Unchanged => {}
+            IndexIncrement::ByX => self.index_register = self.index_register.wrapping_add(x),
+            IndexIncrement::ByXPlusOne => {
+                self.index_register = self.index_register.wrapping_add(x + 1)
+            }
         }
     }
 
@@ -372,100 +615,436 @@ impl Interpreter {
         }
 
         if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                //BEEP
+            self.sound_timer -= 1;
+        }
+    }
+
+    /// Runs one 60 Hz frame: `ticks_per_frame` instructions or a frame's worth of
+    /// VIP cycles, depending on the timing mode, or less if the program halts,
+    /// faults or draws under the `display_wait` quirk. Then decrements the timers.
+    /// The summary's changed rows cover everything since the previous frame,
+    /// including resets and restored states.
+    pub fn run_frame(&mut self) -> FrameSummary {
+        self.begin_frame();
+        let mut instructions = 0;
+        let mut fault = None;
+        while !self.is_frame_over(instructions) {
+            if let Err(err) = self.tick() {
+                fault = Some(err);
+                break;
             }
+            instructions += 1;
+        }
+        self.end_frame(instructions, fault)
+    }
 
-            self.sound_timer -= 1;
+    pub(crate) fn begin_frame(&mut self) {
+        self.waiting_for_vblank = false;
+    }
+
+    pub(crate) fn is_frame_over(&self, instructions: usize) -> bool {
+        let budget_spent = match self.timing {
+            Timing::Uniform => instructions >= self.ticks_per_frame,
+            Timing::Vip => self.cycles_left <= 0,
+        };
+        budget_spent || self.halted || self.waiting_for_vblank
+    }
+
+    pub(crate) fn end_frame(
+        &mut self,
+        instructions: usize,
+        fault: Option<Chip8Error>,
+    ) -> FrameSummary {
+        let status = match fault {
+            Some(err) => FrameStatus::Fault(err),
+            None => {
+                // unused cycles are spent waiting; only an overrun carries over
+                self.cycles_left = self.cycles_left.min(0) + VIP_CYCLES_PER_FRAME;
+                self.tick_timers();
+                if self.halted {
+                    FrameStatus::Halted
+                } else {
+                    FrameStatus::Running
+                }
+            }
+        };
+        let changed_rows = self.framebuffer.take_changed_rows();
+        FrameSummary {
+            instructions,
+            screen_changed: changed_rows != 0,
+            changed_rows,
+            sound_active: self.is_buzzer_active(),
+            status,
         }
     }
 
+    pub fn ticks_per_frame(&self) -> usize {
+        self.ticks_per_frame
+    }
+
+    pub fn set_ticks_per_frame(&mut self, ticks: usize) {
+        self.ticks_per_frame = ticks;
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    /// The buzzer sounds for as long as the sound timer is non-zero.
+    pub fn is_buzzer_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Renders the buzzer into `out` as samples from -1 to 1: the XO-CHIP pattern
+    /// if one was loaded, a square tone otherwise. Consecutive calls continue the
+    /// same waveform, so a frontend can render one buffer per frame.
+    pub fn fill_audio(&mut self, out: &mut [f32], sample_rate: u32) {
+        let active = self.is_buzzer_active();
+        self.voice.render(active, out, sample_rate);
+    }
+
+    /// Frequency of the tone played when no XO-CHIP pattern is loaded.
+    pub fn buzzer_frequency(&self) -> f32 {
+        self.voice.frequency
+    }
+
+    pub fn set_buzzer_frequency(&mut self, frequency: f32) {
+        self.voice.frequency = frequency.max(0.0);
+    }
+
+    pub fn audio_pattern(&self) -> Option<&[u8; PATTERN_SIZE]> {
+        self.voice.pattern.as_ref()
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.voice.pitch
+    }
+
     pub fn reset(&mut self) {
         self.program_counter = 0;
         self.index_register = 0;
         self.stack_pointer = 0;
         self.stack = [0; STACK_SIZE];
         self.registers = [0; NUMBER_OF_REGISTERS];
-        self.memory = [0; MEMORY_SIZE];
+        self.memory.fill(0);
         self.delay_timer = 0;
         self.sound_timer = 0;
-        self.screen = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
+        self.framebuffer.clear();
+        self.planes = 1;
+        self.hires = false;
+        self.halted = false;
         self.keys = [false; NUMBER_OF_KEYS];
+        self.flags = [0; NUMBER_OF_FLAGS];
+        self.rng.reseed(self.seed);
+        self.rom_hash = 0;
+        self.voice.reset();
+        self.waiting_for_vblank = false;
+        self.cycles_left = VIP_CYCLES_PER_FRAME;
+
+        self.load_fonts();
+    }
+
+    pub fn load(&mut self, program: &[u8]) -> Result<(), Chip8Error> {
+        let start = PROGRAM_START as usize;
+        let max = self.memory.len() - start;
+        if program.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: program.len(),
+                max,
+            });
+        }
 
-        self.memory[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        self.memory[start..start + program.len()].copy_from_slice(program);
+        self.program_counter = PROGRAM_START;
+        self.rom_hash = fnv1a(program);
+        Ok(())
+    }
+
+    fn fetch(&mut self) -> Result<u16, Chip8Error> {
+        let opcode = self.mem_read_16(self.program_counter)?;
+        self.program_counter = self.program_counter.wrapping_add(2);
+        Ok(opcode)
+    }
+
+    fn execute(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let invalid = Chip8Error::InvalidOpcode {
+            pc: self.program_counter.wrapping_sub(2),
+            opcode,
+        };
+        let instruction = decode(opcode).ok_or(invalid)?;
+
+        match instruction {
+            Instruction::Sys(0) => {}
+            Instruction::ScrollDown(n) => self.scroll_down(n),
+            Instruction::ScrollUp(n) if self.xo_chip => self.scroll_up(n),
+            Instruction::Cls => self.cls(),
+            Instruction::Ret => self.ret()?,
+            Instruction::ScrollRight => self.scroll_right(),
+            Instruction::ScrollLeft => self.scroll_left(),
+            Instruction::Exit => self.exit(),
+            Instruction::Lores => self.lores(),
+            Instruction::Hires => self.hires(),
+            Instruction::Jp(addr) => self.jp_to_addr(addr),
+            Instruction::Call(addr) => self.call_at_addr(addr)?,
+            Instruction::SeByte { x, byte } => self.se_vx_and_byte(x, byte),
+            Instruction::SneByte { x, byte } => self.sne_vx_and_byte(x, byte),
+            Instruction::SeReg { x, y } => self.se_vx_and_vy(x, y),
+            Instruction::SaveRange { x, y } if self.xo_chip => {
+                self.ld_mem_with_register_range(x, y)?
+            }
+            Instruction::LoadRange { x, y } if self.xo_chip => {
+                self.ld_register_range_with_mem(x, y)?
+            }
+            Instruction::LdByte { x, byte } => self.ld_vx_with_byte(x, byte),
+            Instruction::AddByte { x, byte } => self.add_vx_with_byte(x, byte),
+            Instruction::LdReg { x, y } => self.ld_vx_with_vy(x, y),
+            Instruction::Or { x, y } => self.or_vx_with_vy(x, y),
+            Instruction::And { x, y } => self.and_vx_with_vy(x, y),
+            Instruction::Xor { x, y } => self.xor_vx_with_vy(x, y),
+            Instruction::AddReg { x, y } => self.add_vx_with_vy(x, y),
+            Instruction::Sub { x, y } => self.sub_vx_with_vy(x, y),
+            Instruction::Shr { x, y } => self.shr_vx(x, y),
+            Instruction::Subn { x, y } => self.subn_vx_with_vy(x, y),
+            Instruction::Shl { x, y } => self.shl_vx(x, y),
+            Instruction::SneReg { x, y } => self.sne_vx_and_vy(x, y),
+            Instruction::LdI(addr) => self.ld_i_with_addr(addr),
+            Instruction::JpV0(addr) => self.jp_to_v0_plus_addr(addr),
+            Instruction::Rnd { x, byte } => self.rnd(x, byte),
+            Instruction::Drw { x, y, n } => self.draw(x, y, n)?,
+            Instruction::Skp(x) => self.skp(x),
+            Instruction::Sknp(x) => self.sknp(x),
+            Instruction::LdILong(_) if self.xo_chip => self.ld_i_with_long_addr()?,
+            Instruction::Plane(n) if self.xo_chip => self.select_planes(n),
+            Instruction::Audio if self.xo_chip => self.ld_audio_pattern()?,
+            Instruction::Pitch(x) if self.xo_chip => self.ld_pitch_with_vx(x),
+            Instruction::LdVxDt(x) => self.ld_vx_with_dt(x),
+            Instruction::LdVxK(x) => self.ld_vx_with_key_press(x),
+            Instruction::LdDtVx(x) => self.ld_dt_with_vx(x),
+            Instruction::LdStVx(x) => self.ld_st_with_vx(x),
+            Instruction::AddI(x) => self.add_i_with_vx(x),
+            Instruction::LdF(x) => self.ld_i_with_font_address(x),
+            Instruction::LdHf(x) => self.ld_i_with_big_font_address(x),
+            Instruction::LdB(x) => self.ld_bcd(x)?,
+            Instruction::LdMem(x) => self.ld_mem_with_registers(x)?,
+            Instruction::LdRegs(x) => self.ld_registers_with_mem(x)?,
+            Instruction::SaveFlags(x) => self.ld_flags_with_registers(x),
+            Instruction::LoadFlags(x) => self.ld_registers_with_flags(x),
+            _ => return Err(invalid),
+        }
+        Ok(())
     }
 
-    pub fn load(&mut self, program: &[u8]) {
-        for i in 0..program.len() as u16 {
-            self.mem_write(PROGRAM_START + i, program[i as usize]);
+    pub fn tick(&mut self) -> Result<(), Chip8Error> {
+        if self.halted {
+            return Ok(());
         }
-        self.program_counter = PROGRAM_START;
+        if self.tracer.0.is_some() {
+            let entry = self.trace_entry();
+            if let Some(sink) = self.tracer.0.as_mut() {
+                sink.record(&entry);
+            }
+        }
+        let pc = self.program_counter;
+        let opcode = self.fetch()?;
+        if self.timing == Timing::Uniform {
+            return self.execute(opcode);
+        }
+
+        let instruction = decode(opcode);
+        let cycles = instruction.map_or(0, |instruction| vip_cycles(instruction, self));
+        self.execute(opcode)?;
+        let skip_cycles = match instruction {
+            Some(instruction) if self.program_counter.wrapping_sub(pc) > 2 => {
+                vip_skip_cycles(instruction)
+            }
+            _ => 0,
+        };
+        self.cycles_left -= (cycles + skip_cycles) as i32;
+        Ok(())
     }
 
-    fn fetch(&mut self) -> u16 {
-        let opcode = self.mem_read_16(self.program_counter);
-        self.program_counter += 2;
-        opcode
-    }
-
-    fn execute(&mut self, opcode: u16) {
-        let nibble1 = (opcode & 0xF000) >> 12;
-        let nibble2 = (opcode & 0x0F00) >> 8;
-        let nibble3 = (opcode & 0x00F0) >> 4;
-        let nibble4 = opcode & 0x000F;
-
-        match (nibble1, nibble2, nibble3, nibble4) {
-            (0, 0, 0, 0) => println!("NOP"),
-            (0, 0, 0xe, 0) => self.cls(),
-            (0, 0, 0xE, 0xE) => self.ret(),
-            (1, _, _, _) => self.jp_to_addr(opcode),
-            (2, _, _, _) => self.call_at_addr(opcode),
-            (3, _, _, _) => self.se_vx_and_byte(opcode),
-            (4, _, _, _) => self.sne_vx_and_byte(opcode),
-            (5, _, _, _) => self.se_vx_and_vy(opcode),
-            (6, _, _, _) => self.ld_vx_with_byte(opcode),
-            (7, _, _, _) => self.add_vx_with_byte(opcode),
-            (8, _, _, 0) => self.ld_vx_with_vy(opcode),
-            (8, _, _, 1) => self.or_vx_with_vy(opcode),
-            (8, _, _, 2) => self.and_vx_with_vy(opcode),
-            (8, _, _, 3) => self.xor_vx_with_vy(opcode),
-            (8, _, _, 4) => self.add_vx_with_vy(opcode),
-            (8, _, _, 5) => self.sub_vx_with_vy(opcode),
-            (8, _, _, 6) => self.shr_vx(opcode),
-            (8, _, _, 7) => self.subn_vx_with_vy(opcode),
-            (8, _, _, 0xE) => self.shl_vx(opcode),
-            (9, _, _, _) => self.sne_vx_and_vy(opcode),
-            (0xA, _, _, _) => self.ld_i_with_addr(opcode),
-            (0xB, _, _, _) => self.jp_to_v0_plus_addr(opcode),
-            (0xC, _, _, _) => self.rnd(opcode),
-            (0xD, _, _, _) => self.draw(opcode),
-            (0xE, _, 9, 0xE) => self.skp(opcode),
-            (0xE, _, 0xA, 1) => self.sknp(opcode),
-            (0xF, _, 0, 7) => self.ld_vx_with_dt(opcode),
-            (0xF, _, 0, 0xA) => self.ld_vx_with_key_press(opcode),
-            (0xF, _, 1, 5) => self.ld_dt_with_vx(opcode),
-            (0xF, _, 1, 8) => self.ld_st_with_vx(opcode),
-            (0xF, _, 1, 0xE) => self.add_i_with_vx(opcode),
-            (0xF, _, 2, 9) => self.ld_i_with_font_address(opcode),
-            (0xF, _, 3, 3) => self.ld_bcd(opcode),
-            (0xF, _, 5, 5) => self.ld_mem_with_registers(opcode),
-            (0xF, _, 6, 5) => self.ld_registers_with_mem(opcode),
-            (_, _, _, _) => unimplemented!("Opcode not defined: {opcode}"),
-        }
-    }
-
-    pub fn tick(&mut self) {
-        let opcode = self.fetch();
-        self.execute(opcode);
-    }
-
-    pub fn get_screen(&self) -> &[[bool; SCREEN_WIDTH]; SCREEN_HEIGHT] {
-        &self.screen
+    /// Sends every executed instruction to `sink`, or stops tracing with `None`.
+    pub fn set_trace_sink(&mut self, sink: Option<Box<dyn TraceSink>>) {
+        self.tracer = Tracer(sink);
+    }
+
+    /// The state the next `tick` starts from.
+    pub fn trace_entry(&self) -> TraceEntry {
+        let word = |address: u16| self.mem_read_16(address).ok();
+        let opcode = word(self.program_counter).unwrap_or_default();
+        let instruction = match decode(opcode) {
+            Some(Instruction::LdILong(_)) => {
+                word(self.program_counter.wrapping_add(2)).map(Instruction::LdILong)
+            }
+            instruction => instruction,
+        };
+        TraceEntry {
+            pc: self.program_counter,
+            opcode,
+            instruction,
+            registers: self.registers,
+            index_register: self.index_register,
+            stack_pointer: self.stack_pointer,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    /// A copy of the framebuffer with one byte per pixel, for callers that predate
+    /// `framebuffer`. Each pixel holds one bit per bitplane: bit 0 is plane 1, bit 1
+    /// is plane 2.
+    pub fn get_screen(&self) -> [[u8; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT] {
+        let mut screen = [[0; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT];
+        for (y, row) in screen.iter_mut().enumerate() {
+            for (pixel, value) in row
+                .iter_mut()
+                .zip(self.framebuffer.row_pixels(y, HIRES_SCREEN_WIDTH))
+            {
+                *pixel = value;
+            }
+        }
+        screen
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    /// Rows changed since the last call, row 0 in bit 0. A renderer that only
+    /// redraws these rows stays in sync however the machine was driven.
+    pub fn take_dirty_rows(&mut self) -> u64 {
+        self.framebuffer.take_dirty_rows()
+    }
+
+    /// FNV-1a of the visible pixels row by row, one byte each.
+    pub fn screen_hash(&self) -> u64 {
+        let width = self.screen_width();
+        let pixels: Vec<u8> = (0..self.screen_height())
+            .flat_map(|row| self.framebuffer.row_pixels(row, width))
+            .collect();
+        fnv1a(&pixels)
+    }
+
+    pub fn screen_width(&self) -> usize {
+        if self.hires {
+            HIRES_SCREEN_WIDTH
+        } else {
+            SCREEN_WIDTH
+        }
+    }
+
+    pub fn screen_height(&self) -> usize {
+        if self.hires {
+            HIRES_SCREEN_HEIGHT
+        } else {
+            SCREEN_HEIGHT
+        }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn stack_pointer(&self) -> u8 {
+        self.stack_pointer
+    }
+
+    /// Return addresses currently on the stack, oldest first.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer as usize]
+    }
+
+    pub fn registers(&self) -> &[u8; NUMBER_OF_REGISTERS] {
+        &self.registers
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
     }
 
     pub fn keypress(&mut self, key: usize, pressed: bool) {
         self.keys[key] = pressed;
     }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Switches between the classic 4 KiB machine and XO-CHIP, which adds 64 KiB of
+    /// memory, a second bitplane and the XO-CHIP instructions.
+    pub fn set_xo_chip(&mut self, enabled: bool) {
+        self.xo_chip = enabled;
+        self.memory
+            .resize(if enabled { XO_MEMORY_SIZE } else { MEMORY_SIZE }, 0);
+    }
+
+    pub fn is_xo_chip(&self) -> bool {
+        self.xo_chip
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Reseeds the random source. `reset` goes back to this seed, so runs started
+    /// from the same seed and input are identical.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng.reseed(seed);
+    }
+
+    /// Replaces the random source, reseeding it with the current seed.
+    pub fn set_random_source(&mut self, mut rng: Box<dyn RandomSource>) {
+        rng.reseed(self.seed);
+        self.rng = rng;
+    }
+
+    pub fn random_state(&self) -> u64 {
+        self.rng.state()
+    }
+
+    pub fn set_random_state(&mut self, state: u64) {
+        self.rng.set_state(state);
+    }
+}
+
+/// Registers X through Y inclusive, walking backwards when X > Y.
+fn register_range(x: u8, y: u8) -> impl Iterator<Item = u8> {
+    (0..=x.abs_diff(y)).map(move |offset| if x <= y { x + offset } else { x - offset })
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -475,25 +1054,25 @@ mod tests {
     #[test]
     fn test_memory() {
         let mut interpreter = Interpreter::new();
-        interpreter.mem_write(PROGRAM_START, 0x20);
-        assert_eq!(interpreter.mem_read(PROGRAM_START), 0x20);
+        interpreter.mem_write(PROGRAM_START, 0x20).unwrap();
+        assert_eq!(interpreter.mem_read(PROGRAM_START).unwrap(), 0x20);
     }
 
     #[test]
     fn test_load() {
         let program = [7; 1000];
         let mut interpreter = Interpreter::new();
-        interpreter.load(&program);
+        interpreter.load(&program).unwrap();
         assert_eq!(interpreter.program_counter, PROGRAM_START);
-        assert_eq!(interpreter.mem_read(0x25f), 7);
+        assert_eq!(interpreter.mem_read(0x25f).unwrap(), 7);
     }
 
     #[test]
     fn test_fetch() {
         let program = [0xff; 1000];
         let mut interpreter = Interpreter::new();
-        interpreter.load(&program);
-        let operation = interpreter.fetch();
+        interpreter.load(&program).unwrap();
+        let operation = interpreter.fetch().unwrap();
         assert_eq!(interpreter.program_counter, 0x202);
         assert_eq!(operation, 0xffff);
     }
@@ -501,23 +1080,23 @@ mod tests {
     #[test]
     fn test_cls() {
         let mut interpreter = Interpreter::new();
-        interpreter.screen[5][8] = true;
+        interpreter.framebuffer.set_pixel(8, 5, 1);
         interpreter.cls();
-        assert_eq!(interpreter.screen[5][8], false)
+        assert_eq!(interpreter.framebuffer.pixel(8, 5), 0)
     }
 
     #[test]
     fn test_ret() {
         let mut interpreter = Interpreter::new();
-        interpreter.stack_push(0x1111);
-        interpreter.ret();
+        interpreter.stack_push(0x1111).unwrap();
+        interpreter.ret().unwrap();
         assert_eq!(interpreter.program_counter, 0x1111);
     }
 
     #[test]
     fn test_jp_to_addr() {
         let mut interpreter = Interpreter::new();
-        interpreter.jp_to_addr(0x1111);
+        interpreter.execute(0x1111).unwrap();
         assert_eq!(interpreter.program_counter, 0x0111);
     }
 
@@ -525,8 +1104,8 @@ mod tests {
     fn test_call_at_addr() {
         let mut interpreter = Interpreter::new();
         interpreter.program_counter = PROGRAM_START;
-        interpreter.call_at_addr(0x2222);
-        assert_eq!(interpreter.stack_pop(), 0x0200);
+        interpreter.execute(0x2222).unwrap();
+        assert_eq!(interpreter.stack_pop().unwrap(), 0x0200);
         assert_eq!(interpreter.program_counter, 0x0222)
     }
 
@@ -535,7 +1114,7 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.program_counter = PROGRAM_START;
         interpreter.registers[1] = 0x55;
-        interpreter.se_vx_and_byte(0x3155);
+        interpreter.execute(0x3155).unwrap();
         assert_eq!(interpreter.program_counter, 0x0202)
     }
 
@@ -544,7 +1123,7 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.program_counter = PROGRAM_START;
         interpreter.registers[1] = 0x55;
-        interpreter.sne_vx_and_byte(0x4144);
+        interpreter.execute(0x4144).unwrap();
         assert_eq!(interpreter.program_counter, 0x0202)
     }
 
@@ -554,14 +1133,14 @@ mod tests {
         interpreter.program_counter = PROGRAM_START;
         interpreter.registers[1] = 0x55;
         interpreter.registers[2] = 0x55;
-        interpreter.se_vx_and_vy(0x5120);
+        interpreter.execute(0x5120).unwrap();
         assert_eq!(interpreter.program_counter, 0x0202)
     }
 
     #[test]
     fn test_ld_vx_with_byte() {
         let mut interpreter = Interpreter::new();
-        interpreter.ld_vx_with_byte(0x6130);
+        interpreter.execute(0x6130).unwrap();
         assert_eq!(interpreter.registers[1], 0x0030)
     }
 
@@ -569,7 +1148,7 @@ mod tests {
     fn test_add_vx_with_byte() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[1] = 0x33;
-        interpreter.add_vx_with_byte(0x7130);
+        interpreter.execute(0x7130).unwrap();
         assert_eq!(interpreter.registers[1], 0x0063)
     }
 
@@ -577,7 +1156,7 @@ mod tests {
     fn test_ld_vx_with_vy() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[2] = 0x33;
-        interpreter.ld_vx_with_vy(0x8120);
+        interpreter.execute(0x8120).unwrap();
         assert_eq!(interpreter.registers[1], 0x0033)
     }
 
@@ -586,7 +1165,7 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.registers[7] = 0xe7;
         interpreter.registers[5] = 0x33;
-        interpreter.or_vx_with_vy(0x8751);
+        interpreter.execute(0x8751).unwrap();
         assert_eq!(interpreter.registers[7], 0xF7)
     }
 
@@ -595,7 +1174,7 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.registers[7] = 0xe7;
         interpreter.registers[5] = 0x33;
-        interpreter.and_vx_with_vy(0x8752);
+        interpreter.execute(0x8752).unwrap();
         assert_eq!(interpreter.registers[7], 0x23)
     }
 
@@ -604,7 +1183,7 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.registers[7] = 0xe7;
         interpreter.registers[5] = 0x33;
-        interpreter.xor_vx_with_vy(0x8753);
+        interpreter.execute(0x8753).unwrap();
         assert_eq!(interpreter.registers[7], 0xD4)
     }
 
@@ -613,7 +1192,7 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.registers[7] = 0xe7;
         interpreter.registers[5] = 0x33;
-        interpreter.add_vx_with_vy(0x8754);
+        interpreter.execute(0x8754).unwrap();
         assert_eq!(interpreter.registers[7], 0x1A);
         assert_eq!(interpreter.registers[0xF], 1)
     }
@@ -623,17 +1202,34 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.registers[7] = 0x07;
         interpreter.registers[5] = 0x03;
-        interpreter.add_vx_with_vy(0x8754);
+        interpreter.execute(0x8754).unwrap();
         assert_eq!(interpreter.registers[7], 0x0A);
         assert_eq!(interpreter.registers[0xF], 0)
     }
 
+    #[test]
+    fn test_flag_overrides_result_in_vf() {
+        for (opcode, vf) in [
+            (0x8F54, 1),
+            (0x8F55, 1),
+            (0x8F56, 1),
+            (0x8F57, 0),
+            (0x8F5E, 1),
+        ] {
+            let mut interpreter = Interpreter::new();
+            interpreter.registers[0xF] = 0xFF;
+            interpreter.registers[5] = 0x01;
+            interpreter.execute(opcode).unwrap();
+            assert_eq!(interpreter.registers[0xF], vf, "{opcode:04X}");
+        }
+    }
+
     #[test]
     fn test_sub_vx_with_vy_borrow() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[7] = 0x33;
         interpreter.registers[5] = 0xE7;
-        interpreter.sub_vx_with_vy(0x8755);
+        interpreter.execute(0x8755).unwrap();
         assert_eq!(interpreter.registers[7], 0x4C);
         assert_eq!(interpreter.registers[0xF], 0)
     }
@@ -643,7 +1239,7 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.registers[7] = 0xe7;
         interpreter.registers[5] = 0x33;
-        interpreter.sub_vx_with_vy(0x8755);
+        interpreter.execute(0x8755).unwrap();
         assert_eq!(interpreter.registers[7], 0xB4);
         assert_eq!(interpreter.registers[0xF], 1)
     }
@@ -652,7 +1248,7 @@ mod tests {
     fn test_shr_vx_vf_1() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[5] = 0x33;
-        interpreter.shr_vx(0x8556);
+        interpreter.execute(0x8556).unwrap();
         assert_eq!(interpreter.registers[5], 0x19);
         assert_eq!(interpreter.registers[0xF], 1)
     }
@@ -661,7 +1257,7 @@ mod tests {
     fn test_shr_vx_vf_0() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[5] = 0x32;
-        interpreter.shr_vx(0x8556);
+        interpreter.execute(0x8556).unwrap();
         assert_eq!(interpreter.registers[5], 0x19);
         assert_eq!(interpreter.registers[0xF], 0)
     }
@@ -671,7 +1267,7 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.registers[7] = 0x33;
         interpreter.registers[5] = 0xE7;
-        interpreter.subn_vx_with_vy(0x8757);
+        interpreter.execute(0x8757).unwrap();
         assert_eq!(interpreter.registers[7], 0xB4);
         assert_eq!(interpreter.registers[0xF], 1)
     }
@@ -681,7 +1277,7 @@ mod tests {
         let mut interpreter = Interpreter::new();
         interpreter.registers[7] = 0xe7;
         interpreter.registers[5] = 0x33;
-        interpreter.subn_vx_with_vy(0x8757);
+        interpreter.execute(0x8757).unwrap();
         assert_eq!(interpreter.registers[7], 0x4C);
         assert_eq!(interpreter.registers[0xF], 0)
     }
@@ -690,7 +1286,7 @@ mod tests {
     fn test_shl_vx_vf_1() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[5] = 0xE3;
-        interpreter.shl_vx(0x855E);
+        interpreter.execute(0x855E).unwrap();
         assert_eq!(interpreter.registers[5], 0xC6);
         assert_eq!(interpreter.registers[0xF], 1)
     }
@@ -699,7 +1295,7 @@ mod tests {
     fn test_shl_vx_vf_0() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[5] = 0x32;
-        interpreter.shl_vx(0x855E);
+        interpreter.execute(0x855E).unwrap();
         assert_eq!(interpreter.registers[5], 0x64);
         assert_eq!(interpreter.registers[0xF], 0)
     }
@@ -710,14 +1306,14 @@ mod tests {
         interpreter.program_counter = PROGRAM_START;
         interpreter.registers[1] = 0x55;
         interpreter.registers[2] = 0x54;
-        interpreter.sne_vx_and_vy(0x9120);
+        interpreter.execute(0x9120).unwrap();
         assert_eq!(interpreter.program_counter, 0x0202)
     }
 
     #[test]
     fn test_ld_i_with_addr() {
         let mut interpreter = Interpreter::new();
-        interpreter.ld_i_with_addr(0xA130);
+        interpreter.execute(0xA130).unwrap();
         assert_eq!(interpreter.index_register, 0x0130)
     }
 
@@ -725,16 +1321,50 @@ mod tests {
     fn test_jp_to_v0_plus_addr() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[0] = 0x46;
-        interpreter.jp_to_v0_plus_addr(0xB111);
+        interpreter.execute(0xB111).unwrap();
         assert_eq!(interpreter.program_counter, 0x0157);
     }
 
     #[test]
     fn test_rnd() {
         let mut interpreter = Interpreter::new();
+        interpreter.set_seed(42);
         interpreter.registers[8] = 0x46;
-        interpreter.rnd(0xC811);
-        assert!(true); // cant really test this with the rng
+        interpreter.execute(0xC8FF).unwrap();
+        assert_eq!(interpreter.registers[8], 0xBD);
+        interpreter.execute(0xC83C).unwrap();
+        assert_eq!(interpreter.registers[8], 0x28);
+    }
+
+    #[test]
+    fn test_rnd_replays_after_reset() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(42);
+        let first: Vec<u8> = (0..8)
+            .map(|_| {
+                interpreter.execute(0xC0FF).unwrap();
+                interpreter.registers[0]
+            })
+            .collect();
+
+        interpreter.reset();
+        let second: Vec<u8> = (0..8)
+            .map(|_| {
+                interpreter.execute(0xC0FF).unwrap();
+                interpreter.registers[0]
+            })
+            .collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_clone_keeps_random_state() {
+        let mut interpreter = Interpreter::new();
+        interpreter.execute(0xC0FF).unwrap();
+        let mut snapshot = interpreter.clone();
+        interpreter.execute(0xC0FF).unwrap();
+        snapshot.execute(0xC0FF).unwrap();
+        assert_eq!(interpreter.registers[0], snapshot.registers[0]);
     }
 
     #[test]
@@ -746,7 +1376,7 @@ mod tests {
         let sprite = [0xF0, 0x90, 0x90, 0x90, 0xF0];
         interpreter.memory[PROGRAM_START as usize..PROGRAM_START as usize + sprite.len()]
             .copy_from_slice(&sprite);
-        interpreter.draw(0xD125);
+        interpreter.execute(0xD125).unwrap();
 
         let coordinates = [
             (16, 32),
@@ -766,7 +1396,7 @@ mod tests {
         ];
 
         for (r, c) in coordinates {
-            assert!(interpreter.screen[r][c])
+            assert_ne!(interpreter.framebuffer.pixel(c, r), 0)
         }
         assert_eq!(interpreter.registers[0xF], 0)
     }
@@ -777,7 +1407,7 @@ mod tests {
         interpreter.program_counter = PROGRAM_START;
         interpreter.registers[1] = 0x01;
         interpreter.keys[1] = true;
-        interpreter.skp(0xE19E);
+        interpreter.execute(0xE19E).unwrap();
         assert_eq!(interpreter.program_counter, 0x202);
     }
 
@@ -787,7 +1417,7 @@ mod tests {
         interpreter.program_counter = PROGRAM_START;
         interpreter.registers[1] = 0x01;
         interpreter.keys[1] = false;
-        interpreter.sknp(0xE1A1);
+        interpreter.execute(0xE1A1).unwrap();
         assert_eq!(interpreter.program_counter, 0x202);
     }
 
@@ -795,7 +1425,7 @@ mod tests {
     fn test_ld_vx_with_dt() {
         let mut interpreter = Interpreter::new();
         interpreter.delay_timer = 2;
-        interpreter.ld_vx_with_dt(0xF107);
+        interpreter.execute(0xF107).unwrap();
         assert_eq!(interpreter.registers[1], 0x02);
     }
 
@@ -803,7 +1433,7 @@ mod tests {
     fn test_ld_vx_with_key_press() {
         let mut interpreter = Interpreter::new();
         interpreter.keys[1] = true;
-        interpreter.ld_vx_with_key_press(0xF10A);
+        interpreter.execute(0xF10A).unwrap();
         assert_eq!(interpreter.registers[1], 0x01);
     }
 
@@ -811,7 +1441,7 @@ mod tests {
     fn test_ld_dt_with_vx() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[1] = 2;
-        interpreter.ld_dt_with_vx(0xF115);
+        interpreter.execute(0xF115).unwrap();
         assert_eq!(interpreter.delay_timer, 0x02);
     }
 
@@ -819,16 +1449,167 @@ mod tests {
     fn test_ld_st_with_vx() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[1] = 2;
-        interpreter.ld_st_with_vx(0xF118);
+        interpreter.execute(0xF118).unwrap();
         assert_eq!(interpreter.sound_timer, 0x02);
     }
 
+    #[test]
+    fn test_ld_audio_pattern() {
+        let mut interpreter = Interpreter::new();
+        interpreter.index_register = 0x300;
+        interpreter.memory[0x300..0x310].copy_from_slice(&[0xAA; 16]);
+        assert!(interpreter.execute(0xF002).is_err());
+
+        interpreter.set_xo_chip(true);
+        interpreter.execute(0xF002).unwrap();
+        assert_eq!(interpreter.audio_pattern(), Some(&[0xAA; 16]));
+        interpreter.registers[3] = 112;
+        interpreter.execute(0xF33A).unwrap();
+        assert_eq!(interpreter.pitch(), 112);
+
+        interpreter.reset();
+        assert_eq!(interpreter.audio_pattern(), None);
+        assert_eq!(interpreter.pitch(), 64);
+    }
+
+    #[test]
+    fn test_fill_audio() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_xo_chip(true);
+        let mut samples = [1.0; 8];
+        interpreter.fill_audio(&mut samples, 4000);
+        assert_eq!(samples, [0.0; 8]);
+
+        // a pattern of alternating bits at 4000 bits per second
+        interpreter.memory[0x300..0x310].copy_from_slice(&[0xAA; 16]);
+        interpreter.index_register = 0x300;
+        interpreter.execute(0xF002).unwrap();
+        interpreter.sound_timer = 1;
+        interpreter.fill_audio(&mut samples, 4000);
+        assert_eq!(samples, [1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_buzzer_follows_sound_timer() {
+        let mut interpreter = Interpreter::new();
+        assert!(!interpreter.is_buzzer_active());
+        interpreter.registers[1] = 2;
+        interpreter.execute(0xF118).unwrap();
+        assert!(interpreter.is_buzzer_active());
+        interpreter.tick_timers();
+        assert!(interpreter.is_buzzer_active());
+        interpreter.tick_timers();
+        assert!(!interpreter.is_buzzer_active());
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut interpreter = Interpreter::new();
+        // V0 := 3, delay := V0, sound := V0, then loop
+        interpreter
+            .load(&[0x60, 0x03, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06])
+            .unwrap();
+        let summary = interpreter.run_frame();
+        assert_eq!(
+            summary,
+            FrameSummary {
+                instructions: 5,
+                // a new display counts as changed
+                screen_changed: true,
+                changed_rows: u64::MAX,
+                sound_active: true,
+                status: FrameStatus::Running,
+            }
+        );
+        assert_eq!(interpreter.delay_timer(), 2);
+
+        interpreter.set_ticks_per_frame(20);
+        assert_eq!(interpreter.run_frame().instructions, 20);
+    }
+
+    #[test]
+    fn test_run_frame_display_wait() {
+        // two sprites, then loop
+        let program = [0xD0, 0x05, 0xD0, 0x05, 0x12, 0x04];
+        let mut interpreter = Interpreter::with_quirks(Quirks::vip());
+        interpreter.load(&program).unwrap();
+        let summary = interpreter.run_frame();
+        assert_eq!(summary.instructions, 1);
+        assert!(summary.screen_changed);
+        assert_eq!(interpreter.program_counter(), 0x202);
+        assert_eq!(interpreter.run_frame().instructions, 1);
+        assert!(!interpreter.run_frame().screen_changed);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&program).unwrap();
+        assert_eq!(interpreter.run_frame().instructions, 5);
+    }
+
+    #[test]
+    fn test_run_frame_halt_and_fault() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x00, 0xFD]).unwrap();
+        let summary = interpreter.run_frame();
+        assert_eq!(summary.instructions, 1);
+        assert_eq!(summary.status, FrameStatus::Halted);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.delay_timer = 5;
+        interpreter.load(&[0xFF, 0xFF]).unwrap();
+        let summary = interpreter.run_frame();
+        assert_eq!(summary.instructions, 0);
+        assert_eq!(
+            summary.status,
+            FrameStatus::Fault(Chip8Error::InvalidOpcode {
+                pc: 0x200,
+                opcode: 0xFFFF
+            })
+        );
+        assert_eq!(interpreter.delay_timer(), 5);
+    }
+
+    #[test]
+    fn test_vip_timing() {
+        // V1 := 1 forever: 46 cycles an instruction
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x61, 0x01, 0x12, 0x00]).unwrap();
+        interpreter.set_timing(Timing::Vip);
+        let first = interpreter.run_frame().instructions;
+        let second = interpreter.run_frame().instructions;
+        assert!(first > 50 && first < 60, "{first} instructions");
+        assert!(second.abs_diff(first) <= 1);
+
+        // a clear screen overruns the frame and the next one pays for it
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x00, 0xE0, 0x12, 0x00]).unwrap();
+        interpreter.set_timing(Timing::Vip);
+        assert_eq!(interpreter.run_frame().instructions, 1);
+        let overrun = 40 + 3078 - VIP_CYCLES_PER_FRAME;
+        assert_eq!(interpreter.cycles_left, VIP_CYCLES_PER_FRAME - overrun);
+    }
+
+    #[test]
+    fn test_vip_timing_charges_taken_skips() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_timing(Timing::Vip);
+        // 3000: skip if V0 == 0
+        interpreter.load(&[0x30, 0x00, 0x30, 0x01]).unwrap();
+        let start = interpreter.cycles_left;
+        interpreter.tick().unwrap();
+        assert_eq!(start - interpreter.cycles_left, 40 + 10 + 4);
+
+        interpreter.program_counter = 0x202;
+        let start = interpreter.cycles_left;
+        interpreter.tick().unwrap();
+        assert_eq!(start - interpreter.cycles_left, 40 + 10);
+    }
+
     #[test]
     fn test_add_i_with_vx() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[1] = 0x02;
         interpreter.index_register = 0x25;
-        interpreter.add_i_with_vx(0xF11E);
+        interpreter.execute(0xF11E).unwrap();
         assert_eq!(interpreter.index_register, 0x27);
     }
 
@@ -836,7 +1617,7 @@ mod tests {
     fn test_ld_i_with_font_address() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[1] = 0x02;
-        interpreter.ld_i_with_font_address(0xF129);
+        interpreter.execute(0xF129).unwrap();
         assert_eq!(interpreter.index_register, 0x0A);
     }
 
@@ -844,10 +1625,10 @@ mod tests {
     fn test_ld_bcd() {
         let mut interpreter = Interpreter::new();
         interpreter.registers[1] = 0x87;
-        interpreter.ld_bcd(0xF133);
-        assert_eq!(interpreter.mem_read(0x0), 0x1);
-        assert_eq!(interpreter.mem_read(0x1), 0x3);
-        assert_eq!(interpreter.mem_read(0x2), 0x5);
+        interpreter.execute(0xF133).unwrap();
+        assert_eq!(interpreter.mem_read(0x0).unwrap(), 0x1);
+        assert_eq!(interpreter.mem_read(0x1).unwrap(), 0x3);
+        assert_eq!(interpreter.mem_read(0x2).unwrap(), 0x5);
     }
 
     #[test]
@@ -856,21 +1637,478 @@ mod tests {
         interpreter.registers[0] = 0x84;
         interpreter.registers[1] = 0x85;
         interpreter.registers[2] = 0x86;
-        interpreter.ld_mem_with_registers(0xF255);
-        assert_eq!(interpreter.mem_read(0x0), 0x84);
-        assert_eq!(interpreter.mem_read(0x1), 0x85);
-        assert_eq!(interpreter.mem_read(0x2), 0x86);
+        interpreter.execute(0xF255).unwrap();
+        assert_eq!(interpreter.mem_read(0x0).unwrap(), 0x84);
+        assert_eq!(interpreter.mem_read(0x1).unwrap(), 0x85);
+        assert_eq!(interpreter.mem_read(0x2).unwrap(), 0x86);
     }
 
     #[test]
     fn test_ld_registers_with_mem() {
         let mut interpreter = Interpreter::new();
-        interpreter.mem_write(0x0, 0x84);
-        interpreter.mem_write(0x1, 0x85);
-        interpreter.mem_write(0x2, 0x86);
-        interpreter.ld_registers_with_mem(0xF265);
+        interpreter.mem_write(0x0, 0x84).unwrap();
+        interpreter.mem_write(0x1, 0x85).unwrap();
+        interpreter.mem_write(0x2, 0x86).unwrap();
+        interpreter.execute(0xF265).unwrap();
         assert_eq!(interpreter.registers[0], 0x84);
         assert_eq!(interpreter.registers[1], 0x85);
         assert_eq!(interpreter.registers[2], 0x86);
     }
+
+    #[test]
+    fn test_shift_quirk_in_place() {
+        let mut interpreter = Interpreter::with_quirks(Quirks {
+            shift_uses_vy: false,
+            ..Quirks::default()
+        });
+        interpreter.registers[5] = 0x32;
+        interpreter.registers[6] = 0x81;
+        interpreter.execute(0x8566).unwrap();
+        assert_eq!(interpreter.registers[5], 0x19);
+        assert_eq!(interpreter.registers[0xF], 0);
+        interpreter.execute(0x856E).unwrap();
+        assert_eq!(interpreter.registers[5], 0x32);
+        assert_eq!(interpreter.registers[0xF], 0)
+    }
+
+    #[test]
+    fn test_shift_quirk_uses_vy() {
+        let mut interpreter = Interpreter::with_quirks(Quirks {
+            shift_uses_vy: true,
+            ..Quirks::default()
+        });
+        interpreter.registers[5] = 0x32;
+        interpreter.registers[6] = 0x81;
+        interpreter.execute(0x8566).unwrap();
+        assert_eq!(interpreter.registers[5], 0x40);
+        assert_eq!(interpreter.registers[0xF], 1);
+        interpreter.execute(0x856E).unwrap();
+        assert_eq!(interpreter.registers[5], 0x02);
+        assert_eq!(interpreter.registers[0xF], 1)
+    }
+
+    #[test]
+    fn test_index_increment_quirk() {
+        let cases = [
+            (IndexIncrement::Unchanged, 0x300),
+            (IndexIncrement::ByX, 0x302),
+            (IndexIncrement::ByXPlusOne, 0x303),
+        ];
+
+        for (index_increment, expected) in cases {
+            let mut interpreter = Interpreter::with_quirks(Quirks {
+                index_increment,
+                ..Quirks::default()
+            });
+            interpreter.index_register = 0x300;
+            interpreter.execute(0xF255).unwrap();
+            assert_eq!(interpreter.index_register, expected);
+
+            interpreter.index_register = 0x300;
+            interpreter.execute(0xF265).unwrap();
+            assert_eq!(interpreter.index_register, expected);
+        }
+    }
+
+    #[test]
+    fn test_jump_quirk_uses_v0() {
+        let mut interpreter = Interpreter::with_quirks(Quirks {
+            jump_uses_vx: false,
+            ..Quirks::default()
+        });
+        interpreter.registers[0] = 0x46;
+        interpreter.registers[2] = 0x10;
+        interpreter.execute(0xB220).unwrap();
+        assert_eq!(interpreter.program_counter, 0x0266);
+    }
+
+    #[test]
+    fn test_jump_quirk_uses_vx() {
+        let mut interpreter = Interpreter::with_quirks(Quirks {
+            jump_uses_vx: true,
+            ..Quirks::default()
+        });
+        interpreter.registers[0] = 0x46;
+        interpreter.registers[2] = 0x10;
+        interpreter.execute(0xB220).unwrap();
+        assert_eq!(interpreter.program_counter, 0x0230);
+    }
+
+    #[test]
+    fn test_logic_quirk_keeps_vf() {
+        let mut interpreter = Interpreter::with_quirks(Quirks {
+            logic_resets_vf: false,
+            ..Quirks::default()
+        });
+        for opcode in [0x8751, 0x8752, 0x8753] {
+            interpreter.registers[0xF] = 1;
+            interpreter.execute(opcode).unwrap();
+            assert_eq!(interpreter.registers[0xF], 1);
+        }
+    }
+
+    #[test]
+    fn test_logic_quirk_resets_vf() {
+        let mut interpreter = Interpreter::with_quirks(Quirks {
+            logic_resets_vf: true,
+            ..Quirks::default()
+        });
+        for opcode in [0x8751, 0x8752, 0x8753] {
+            interpreter.registers[0xF] = 1;
+            interpreter.execute(opcode).unwrap();
+            assert_eq!(interpreter.registers[0xF], 0);
+        }
+    }
+
+    #[test]
+    fn test_draw_quirk_wraps() {
+        let mut interpreter = Interpreter::with_quirks(Quirks {
+            clip_sprites: false,
+            ..Quirks::default()
+        });
+        interpreter.registers[1] = 62;
+        interpreter.registers[2] = 31;
+        interpreter.index_register = PROGRAM_START;
+        interpreter.memory[PROGRAM_START as usize..PROGRAM_START as usize + 2]
+            .copy_from_slice(&[0xF0, 0xF0]);
+        interpreter.execute(0xD122).unwrap();
+        assert_ne!(interpreter.framebuffer.pixel(63, 31), 0);
+        assert_ne!(interpreter.framebuffer.pixel(0, 31), 0);
+        assert_ne!(interpreter.framebuffer.pixel(62, 0), 0);
+        assert_ne!(interpreter.framebuffer.pixel(1, 0), 0);
+    }
+
+    #[test]
+    fn test_draw_quirk_clips() {
+        let mut interpreter = Interpreter::with_quirks(Quirks {
+            clip_sprites: true,
+            ..Quirks::default()
+        });
+        interpreter.registers[1] = 62;
+        interpreter.registers[2] = 31;
+        interpreter.index_register = PROGRAM_START;
+        interpreter.memory[PROGRAM_START as usize..PROGRAM_START as usize + 2]
+            .copy_from_slice(&[0xF0, 0xF0]);
+        interpreter.execute(0xD122).unwrap();
+        assert_ne!(interpreter.framebuffer.pixel(63, 31), 0);
+        assert_eq!(interpreter.framebuffer.pixel(0, 31), 0);
+        assert_eq!(interpreter.framebuffer.pixel(62, 0), 0);
+        assert_eq!(interpreter.framebuffer.pixel(1, 0), 0);
+    }
+
+    #[test]
+    fn test_draw_quirk_wraps_start_coordinates() {
+        let mut interpreter = Interpreter::with_quirks(Quirks::vip());
+        interpreter.registers[1] = 64 + 2;
+        interpreter.registers[2] = 32 + 3;
+        interpreter.index_register = PROGRAM_START;
+        interpreter.memory[PROGRAM_START as usize] = 0x80;
+        interpreter.execute(0xD121).unwrap();
+        assert_ne!(interpreter.framebuffer.pixel(2, 3), 0);
+    }
+
+    #[test]
+    fn test_scroll_down() {
+        let mut interpreter = Interpreter::new();
+        interpreter.framebuffer.set_pixel(3, 0, 1);
+        interpreter.framebuffer.set_pixel(3, 31, 1);
+        interpreter.execute(0x00C2).unwrap();
+        assert_eq!(interpreter.framebuffer.pixel(3, 0), 0);
+        assert_ne!(interpreter.framebuffer.pixel(3, 2), 0);
+        assert_eq!(interpreter.framebuffer.pixel(3, 33), 0);
+    }
+
+    #[test]
+    fn test_scroll_right() {
+        let mut interpreter = Interpreter::new();
+        interpreter.framebuffer.set_pixel(0, 1, 1);
+        interpreter.framebuffer.set_pixel(62, 1, 1);
+        interpreter.scroll_right();
+        assert_eq!(interpreter.framebuffer.pixel(0, 1), 0);
+        assert_ne!(interpreter.framebuffer.pixel(4, 1), 0);
+        assert_eq!(interpreter.framebuffer.pixel(66, 1), 0);
+    }
+
+    #[test]
+    fn test_scroll_left() {
+        let mut interpreter = Interpreter::new();
+        interpreter.hires();
+        interpreter.framebuffer.set_pixel(127, 1, 1);
+        interpreter.framebuffer.set_pixel(2, 1, 1);
+        interpreter.scroll_left();
+        assert_ne!(interpreter.framebuffer.pixel(123, 1), 0);
+        assert_eq!(interpreter.framebuffer.pixel(127, 1), 0);
+        assert_eq!(interpreter.framebuffer.pixel(2, 1), 0);
+    }
+
+    #[test]
+    fn test_exit() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x00, 0xFD, 0x60, 0x01]).unwrap();
+        interpreter.tick().unwrap();
+        assert!(interpreter.is_halted());
+        interpreter.tick().unwrap();
+        assert_eq!(interpreter.program_counter, 0x202);
+        assert_eq!(interpreter.registers[0], 0);
+    }
+
+    #[test]
+    fn test_hires_and_lores() {
+        let mut interpreter = Interpreter::new();
+        interpreter.framebuffer.set_pixel(8, 5, 1);
+        interpreter.hires();
+        assert!(interpreter.is_hires());
+        assert_eq!(interpreter.screen_width(), HIRES_SCREEN_WIDTH);
+        assert_eq!(interpreter.screen_height(), HIRES_SCREEN_HEIGHT);
+        assert_eq!(interpreter.framebuffer.pixel(8, 5), 0);
+
+        interpreter.framebuffer.set_pixel(8, 5, 1);
+        interpreter.lores();
+        assert!(!interpreter.is_hires());
+        assert_eq!(interpreter.screen_width(), SCREEN_WIDTH);
+        assert_eq!(interpreter.screen_height(), SCREEN_HEIGHT);
+        assert_eq!(interpreter.framebuffer.pixel(8, 5), 0);
+    }
+
+    #[test]
+    fn test_screen_hash() {
+        let mut interpreter = Interpreter::new();
+        let blank = interpreter.screen_hash();
+        assert_eq!(blank, fnv1a(&[0; SCREEN_WIDTH * SCREEN_HEIGHT]));
+
+        // pixels outside the lores area don't count
+        interpreter.framebuffer.set_pixel(100, 40, 1);
+        assert_eq!(interpreter.screen_hash(), blank);
+        interpreter.framebuffer.set_pixel(2, 1, 1);
+        assert_ne!(interpreter.screen_hash(), blank);
+    }
+
+    #[test]
+    fn test_draw_large_sprite() {
+        let mut interpreter = Interpreter::new();
+        interpreter.hires();
+        interpreter.registers[1] = 100;
+        interpreter.registers[2] = 40;
+        interpreter.index_register = PROGRAM_START;
+        interpreter.memory[PROGRAM_START as usize..PROGRAM_START as usize + 32].fill(0xFF);
+        interpreter.execute(0xD120).unwrap();
+
+        for row in 40..56 {
+            for col in 100..116 {
+                assert_ne!(interpreter.framebuffer.pixel(col, row), 0);
+            }
+        }
+        assert_eq!(interpreter.framebuffer.pixel(100, 39), 0);
+        assert_eq!(interpreter.framebuffer.pixel(116, 40), 0);
+        assert_eq!(interpreter.registers[0xF], 0);
+
+        interpreter.execute(0xD120).unwrap();
+        assert_eq!(interpreter.framebuffer.pixel(100, 40), 0);
+        assert_eq!(interpreter.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_ld_i_with_big_font_address() {
+        let mut interpreter = Interpreter::new();
+        interpreter.registers[1] = 0x02;
+        interpreter.execute(0xF130).unwrap();
+        assert_eq!(interpreter.index_register, 0x64);
+        assert_eq!(interpreter.mem_read(0x64).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut interpreter = Interpreter::new();
+        interpreter.registers[0] = 0x84;
+        interpreter.registers[1] = 0x85;
+        interpreter.execute(0xF175).unwrap();
+        interpreter.registers[0] = 0;
+        interpreter.registers[1] = 0;
+        interpreter.execute(0xF185).unwrap();
+        assert_eq!(interpreter.registers[0], 0x84);
+        assert_eq!(interpreter.registers[1], 0x85);
+    }
+
+    #[test]
+    fn test_xo_chip_memory() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.memory.len(), MEMORY_SIZE);
+        interpreter.set_xo_chip(true);
+        assert_eq!(interpreter.memory.len(), XO_MEMORY_SIZE);
+        interpreter.mem_write(0xFFFF, 0x12).unwrap();
+        assert_eq!(interpreter.mem_read(0xFFFF).unwrap(), 0x12);
+    }
+
+    #[test]
+    fn test_ld_i_with_long_addr() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_xo_chip(true);
+        interpreter.load(&[0xF0, 0x00, 0xAB, 0xCD]).unwrap();
+        interpreter.tick().unwrap();
+        assert_eq!(interpreter.index_register, 0xABCD);
+        assert_eq!(interpreter.program_counter, 0x204);
+    }
+
+    #[test]
+    fn test_skip_over_long_load() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_xo_chip(true);
+        interpreter
+            .load(&[0x30, 0x00, 0xF0, 0x00, 0xAB, 0xCD])
+            .unwrap();
+        interpreter.tick().unwrap();
+        assert_eq!(interpreter.program_counter, 0x206);
+    }
+
+    #[test]
+    fn test_ld_mem_with_register_range() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_xo_chip(true);
+        interpreter.registers[2] = 0x84;
+        interpreter.registers[3] = 0x85;
+        interpreter.registers[4] = 0x86;
+        interpreter.index_register = 0x300;
+        interpreter.execute(0x5242).unwrap();
+        assert_eq!(interpreter.mem_read(0x300).unwrap(), 0x84);
+        assert_eq!(interpreter.mem_read(0x302).unwrap(), 0x86);
+        interpreter.execute(0x5422).unwrap();
+        assert_eq!(interpreter.mem_read(0x300).unwrap(), 0x86);
+        assert_eq!(interpreter.mem_read(0x302).unwrap(), 0x84);
+        assert_eq!(interpreter.index_register, 0x300);
+    }
+
+    #[test]
+    fn test_ld_register_range_with_mem() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_xo_chip(true);
+        interpreter.mem_write(0x300, 0x84).unwrap();
+        interpreter.mem_write(0x301, 0x85).unwrap();
+        interpreter.index_register = 0x300;
+        interpreter.execute(0x5673).unwrap();
+        assert_eq!(interpreter.registers[6], 0x84);
+        assert_eq!(interpreter.registers[7], 0x85);
+        interpreter.execute(0x5763).unwrap();
+        assert_eq!(interpreter.registers[7], 0x84);
+        assert_eq!(interpreter.registers[6], 0x85);
+    }
+
+    #[test]
+    fn test_draw_both_planes() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_xo_chip(true);
+        interpreter.execute(0xF301).unwrap();
+        interpreter.index_register = PROGRAM_START;
+        interpreter.memory[PROGRAM_START as usize..PROGRAM_START as usize + 2]
+            .copy_from_slice(&[0x80, 0xC0]);
+        interpreter.execute(0xD011).unwrap();
+        assert_eq!(interpreter.framebuffer.pixel(0, 0), 0b11);
+        assert_eq!(interpreter.framebuffer.pixel(1, 0), 0b10);
+
+        interpreter.execute(0xF201).unwrap();
+        interpreter.execute(0xD011).unwrap();
+        assert_eq!(interpreter.framebuffer.pixel(0, 0), 0b01);
+        assert_eq!(interpreter.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_cls_selected_plane() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_xo_chip(true);
+        interpreter.framebuffer.set_pixel(3, 3, 0b11);
+        interpreter.execute(0xF201).unwrap();
+        interpreter.cls();
+        assert_eq!(interpreter.framebuffer.pixel(3, 3), 0b01);
+    }
+
+    #[test]
+    fn test_scroll_up() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_xo_chip(true);
+        interpreter.framebuffer.set_pixel(3, 5, 0b11);
+        interpreter.execute(0xF101).unwrap();
+        interpreter.execute(0x00D2).unwrap();
+        assert_eq!(interpreter.framebuffer.pixel(3, 3), 0b01);
+        assert_eq!(interpreter.framebuffer.pixel(3, 5), 0b10);
+    }
+
+    #[test]
+    fn test_invalid_opcode() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x60, 0x01, 0x80, 0x08]).unwrap();
+        interpreter.tick().unwrap();
+        assert_eq!(
+            interpreter.tick(),
+            Err(Chip8Error::InvalidOpcode {
+                pc: 0x202,
+                opcode: 0x8008
+            })
+        );
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x22, 0x00]).unwrap();
+        for _ in 0..STACK_SIZE {
+            interpreter.tick().unwrap();
+        }
+        assert_eq!(
+            interpreter.tick(),
+            Err(Chip8Error::StackOverflow { pc: 0x200 })
+        );
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x00, 0xEE]).unwrap();
+        assert_eq!(
+            interpreter.tick(),
+            Err(Chip8Error::StackUnderflow { pc: 0x200 })
+        );
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let mut interpreter = Interpreter::new();
+        interpreter.index_register = 0xFFE;
+        interpreter.registers[1] = 123;
+        assert_eq!(
+            interpreter.execute(0xF133),
+            Err(Chip8Error::MemoryOutOfBounds { address: 0x1000 })
+        );
+        assert_eq!(
+            interpreter.mem_read(0x1000),
+            Err(Chip8Error::MemoryOutOfBounds { address: 0x1000 })
+        );
+    }
+
+    #[test]
+    fn test_skp_masks_key_to_nibble() {
+        let mut interpreter = Interpreter::new();
+        // V0 := 0x21; SKP V0; SKNP V0
+        interpreter
+            .load(&[0x60, 0x21, 0xE0, 0x9E, 0xE0, 0xA1])
+            .unwrap();
+        interpreter.keys[1] = true;
+        interpreter.tick().unwrap();
+        assert_eq!(interpreter.tick(), Ok(()));
+        assert_eq!(interpreter.program_counter, 0x206);
+        interpreter.program_counter = 0x204;
+        assert_eq!(interpreter.tick(), Ok(()));
+        assert_eq!(interpreter.program_counter, 0x206);
+    }
+
+    #[test]
+    fn test_rom_too_large() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.load(&[0; 3585]),
+            Err(Chip8Error::RomTooLarge {
+                size: 3585,
+                max: 3584
+            })
+        );
+        assert!(interpreter.load(&[0; 3584]).is_ok());
+    }
 }
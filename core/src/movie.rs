@@ -0,0 +1,319 @@
+use crate::{
+    fnv1a,
+    state::{StateReader, StateWriter},
+    Desync, IndexIncrement, Interpreter, MovieError, Quirks, Timing,
+};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+pub const MOVIE_VERSION: u16 = 1;
+
+/// The input of one frame and the machine it led to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieFrame {
+    /// Keys held during the frame, key 0 in bit 0.
+    pub keys: u16,
+    /// `fnv1a` of the save state at the end of the frame.
+    pub state_hash: u64,
+}
+
+/// An input recording: the settings a run started with, then the keypad of every
+/// frame. Replaying it from a freshly loaded ROM gives the same run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub quirks: Quirks,
+    pub xo_chip: bool,
+    pub ticks_per_frame: usize,
+    pub timing: Timing,
+    pub seed: u64,
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    /// Starts an empty movie with the interpreter's settings. Call it right after
+    /// loading the ROM, before the first frame.
+    pub fn new(interpreter: &Interpreter) -> Movie {
+        Movie {
+            rom_hash: interpreter.rom_hash(),
+            quirks: interpreter.quirks(),
+            xo_chip: interpreter.is_xo_chip(),
+            ticks_per_frame: interpreter.ticks_per_frame(),
+            timing: interpreter.timing(),
+            seed: interpreter.seed(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Call after every frame to record the keys it ran with.
+    pub fn record(&mut self, interpreter: &Interpreter) {
+        self.frames.push(MovieFrame {
+            keys: interpreter.keys_mask(),
+            state_hash: state_hash(interpreter),
+        });
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = StateWriter {
+            data: Vec::with_capacity(40 + self.frames.len() * 10),
+        };
+        writer.bytes(MAGIC);
+        writer.u16(MOVIE_VERSION);
+        writer.u64(self.rom_hash);
+        writer.u64(self.seed);
+        writer.bool(self.xo_chip);
+        write_quirks(&mut writer, self.quirks);
+        writer.u8(match self.timing {
+            Timing::Uniform => 0,
+            Timing::Vip => 1,
+        });
+        writer.u32(self.ticks_per_frame as u32);
+
+        writer.u32(self.frames.len() as u32);
+        for frame in &self.frames {
+            writer.u16(frame.keys);
+            writer.u64(frame.state_hash);
+        }
+        writer.data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        let mut reader = StateReader { data };
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(MovieError::InvalidMagic);
+        }
+        let version = reader.u16()?;
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let rom_hash = reader.u64()?;
+        let seed = reader.u64()?;
+        let xo_chip = reader.bool()?;
+        let quirks = read_quirks(&mut reader)?;
+        let timing = match reader.u8()? {
+            0 => Timing::Uniform,
+            1 => Timing::Vip,
+            _ => return Err(MovieError::Corrupt),
+        };
+        let ticks_per_frame = reader.u32()? as usize;
+
+        let len = reader.u32()? as usize;
+        let mut frames = Vec::with_capacity(len.min(reader.data.len() / 10));
+        for _ in 0..len {
+            frames.push(MovieFrame {
+                keys: reader.u16()?,
+                state_hash: reader.u64()?,
+            });
+        }
+        if !reader.data.is_empty() {
+            return Err(MovieError::Corrupt);
+        }
+
+        Ok(Movie {
+            rom_hash,
+            quirks,
+            xo_chip,
+            ticks_per_frame,
+            timing,
+            seed,
+            frames,
+        })
+    }
+}
+
+/// The hash movies check every frame against.
+fn state_hash(interpreter: &Interpreter) -> u64 {
+    fnv1a(&interpreter.save_state())
+}
+
+fn write_quirks(writer: &mut StateWriter, quirks: Quirks) {
+    let flags = [
+        quirks.shift_uses_vy,
+        quirks.jump_uses_vx,
+        quirks.logic_resets_vf,
+        quirks.clip_sprites,
+        quirks.display_wait,
+    ];
+    writer.u8(flags
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (bit, &flag)| bits | (flag as u8) << bit));
+    writer.u8(match quirks.index_increment {
+        IndexIncrement::Unchanged => 0,
+        IndexIncrement::ByX => 1,
+        IndexIncrement::ByXPlusOne => 2,
+    });
+}
+
+fn read_quirks(reader: &mut StateReader) -> Result<Quirks, MovieError> {
+    let bits = reader.u8()?;
+    if bits >> 5 != 0 {
+        return Err(MovieError::Corrupt);
+    }
+    let flag = |bit: u8| bits & (1 << bit) != 0;
+    let index_increment = match reader.u8()? {
+        0 => IndexIncrement::Unchanged,
+        1 => IndexIncrement::ByX,
+        2 => IndexIncrement::ByXPlusOne,
+        _ => return Err(MovieError::Corrupt),
+    };
+    Ok(Quirks {
+        shift_uses_vy: flag(0),
+        index_increment,
+        jump_uses_vx: flag(1),
+        logic_resets_vf: flag(2),
+        clip_sprites: flag(3),
+        display_wait: flag(4),
+    })
+}
+
+/// Replays a movie: sets the keypad before every frame and checks the machine after
+/// it.
+#[derive(Debug, Clone)]
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: usize,
+    desynced: bool,
+}
+
+impl MoviePlayer {
+    /// Applies the movie's settings to `interpreter`, which must have just loaded the
+    /// ROM the movie was recorded with.
+    pub fn new(movie: Movie, interpreter: &mut Interpreter) -> Result<MoviePlayer, MovieError> {
+        if movie.rom_hash != interpreter.rom_hash() {
+            return Err(MovieError::RomMismatch {
+                expected: interpreter.rom_hash(),
+                found: movie.rom_hash,
+            });
+        }
+        interpreter.set_quirks(movie.quirks);
+        interpreter.set_xo_chip(movie.xo_chip);
+        interpreter.set_ticks_per_frame(movie.ticks_per_frame);
+        interpreter.set_timing(movie.timing);
+        interpreter.set_seed(movie.seed);
+        Ok(MoviePlayer {
+            movie,
+            frame: 0,
+            desynced: false,
+        })
+    }
+
+    /// Sets the keys for the next frame. Returns `false` once the movie has ended.
+    pub fn feed(&mut self, interpreter: &mut Interpreter) -> bool {
+        match self.movie.frames.get(self.frame) {
+            Some(frame) => {
+                interpreter.set_keys_mask(frame.keys);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Call after every frame `feed` started. Returns the first frame whose state
+    /// differs from the recording, once; later frames are not reported.
+    pub fn verify(&mut self, interpreter: &Interpreter) -> Option<Desync> {
+        let expected = self.movie.frames.get(self.frame)?.state_hash;
+        let frame = self.frame;
+        self.frame += 1;
+        let found = state_hash(interpreter);
+        if found == expected || self.desynced {
+            return None;
+        }
+        self.desynced = true;
+        Some(Desync {
+            frame,
+            expected,
+            found,
+        })
+    }
+
+    /// Frames played so far.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // waits for a key, draws it as a digit and rolls a random number
+    const ROM: [u8; 10] = [0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0xC2, 0xFF, 0x12, 0x00];
+
+    fn interpreter() -> Interpreter {
+        let mut interpreter = Interpreter::with_quirks(Quirks::vip());
+        interpreter.set_seed(42);
+        interpreter.set_ticks_per_frame(7);
+        interpreter.load(&ROM).unwrap();
+        interpreter
+    }
+
+    fn record() -> Movie {
+        let mut interpreter = interpreter();
+        let mut movie = Movie::new(&interpreter);
+        for frame in 0..40 {
+            interpreter.keypress(frame / 10, frame % 10 < 3);
+            interpreter.keypress((frame / 10).saturating_sub(1), false);
+            interpreter.run_frame();
+            movie.record(&interpreter);
+        }
+        movie
+    }
+
+    fn play(movie: Movie, interpreter: &mut Interpreter) -> Option<Desync> {
+        let mut player = MoviePlayer::new(movie, interpreter).unwrap();
+        let mut desync = None;
+        while player.feed(interpreter) {
+            interpreter.run_frame();
+            desync = desync.or(player.verify(interpreter));
+        }
+        desync
+    }
+
+    #[test]
+    fn test_round_trip_bytes() {
+        let movie = record();
+        let bytes = movie.to_bytes();
+        assert_eq!(Movie::from_bytes(&bytes), Ok(movie));
+        assert_eq!(
+            Movie::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MovieError::Truncated)
+        );
+        assert_eq!(Movie::from_bytes(b"C8SS"), Err(MovieError::InvalidMagic));
+    }
+
+    #[test]
+    fn test_playback_matches_recording() {
+        let movie = record();
+        assert!(movie.frames.iter().any(|frame| frame.keys != 0));
+
+        // playback restores the recorded settings
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(1);
+        interpreter.load(&ROM).unwrap();
+        assert_eq!(play(movie, &mut interpreter), None);
+        assert_eq!(interpreter.seed(), 42);
+    }
+
+    #[test]
+    fn test_reports_first_desync() {
+        let mut movie = record();
+        movie.frames[12].keys ^= 1 << 7;
+        let mut interpreter = interpreter();
+        let desync = play(movie, &mut interpreter).unwrap();
+        assert_eq!(desync.frame, 12);
+    }
+
+    #[test]
+    fn test_rejects_other_rom() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x12, 0x00]).unwrap();
+        assert!(matches!(
+            MoviePlayer::new(record(), &mut interpreter),
+            Err(MovieError::RomMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{FULL_INTENSITY, PLANES};
+
+/// An RGB colour.
+pub type Rgb = [u8; 3];
+
+/// The colours pixel values are drawn in. Entry 0 is the background and entry 1 the
+/// foreground of monochrome games; XO-CHIP uses entry 2 for the second plane and 3
+/// where both planes are lit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: [Rgb; 1 << PLANES],
+}
+
+impl Palette {
+    /// White on black, with greys for the XO-CHIP planes.
+    pub const CLASSIC: Palette = Palette {
+        name: "classic",
+        colors: [[0, 0, 0], [255, 255, 255], [170, 170, 170], [85, 85, 85]],
+    };
+
+    /// The built-in themes, in the order frontends cycle through them.
+    pub const THEMES: [Palette; 5] = [
+        Palette::CLASSIC,
+        Palette {
+            name: "lcd",
+            colors: [[155, 188, 15], [15, 56, 15], [48, 98, 48], [139, 172, 15]],
+        },
+        Palette {
+            name: "amber",
+            colors: [[26, 15, 0], [255, 176, 0], [204, 122, 0], [102, 61, 0]],
+        },
+        Palette {
+            name: "octo",
+            colors: [[153, 102, 0], [255, 204, 0], [255, 102, 0], [102, 34, 0]],
+        },
+        Palette {
+            name: "high-contrast",
+            colors: [[0, 0, 0], [255, 255, 255], [255, 255, 0], [0, 255, 255]],
+        },
+    ];
+
+    /// Looks up a built-in theme by name: `classic`, `lcd`, `amber`, `octo` or
+    /// `high-contrast`.
+    pub fn from_name(name: &str) -> Option<Palette> {
+        let name = name.to_ascii_lowercase();
+        Palette::THEMES
+            .into_iter()
+            .find(|palette| palette.name == name)
+    }
+
+    /// The built-in theme after this one, wrapping around.
+    pub fn next(&self) -> Palette {
+        let index = Palette::THEMES
+            .iter()
+            .position(|palette| palette == self)
+            .map_or(0, |index| index + 1);
+        Palette::THEMES[index % Palette::THEMES.len()]
+    }
+
+    pub fn background(&self) -> Rgb {
+        self.colors[0]
+    }
+
+    pub fn color(&self, pixel: u8) -> Rgb {
+        self.colors[pixel as usize]
+    }
+
+    /// The colour of a pixel shown at a phosphor intensity, mixed with the background.
+    pub fn shade(&self, pixel: u8, level: u8) -> Rgb {
+        let background = self.background();
+        let color = self.color(pixel);
+        std::array::from_fn(|channel| {
+            let (from, to) = (background[channel] as i32, color[channel] as i32);
+            (from + (to - from) * level as i32 / FULL_INTENSITY as i32) as u8
+        })
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::CLASSIC
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shade_mixes_with_background() {
+        let palette = Palette::from_name("Octo").unwrap();
+        assert_eq!(palette.shade(1, FULL_INTENSITY), [255, 204, 0]);
+        assert_eq!(palette.shade(1, 0), [153, 102, 0]);
+        assert_eq!(palette.shade(3, 127), [128, 69, 0]);
+        assert_eq!(Palette::CLASSIC.shade(1, 127), [127, 127, 127]);
+    }
+
+    #[test]
+    fn test_themes_cycle() {
+        let mut palette = Palette::default();
+        for theme in Palette::THEMES.iter().skip(1) {
+            palette = palette.next();
+            assert_eq!(&palette, theme);
+        }
+        assert_eq!(palette.next(), Palette::CLASSIC);
+        assert_eq!(Palette::from_name("vga"), None);
+    }
+}
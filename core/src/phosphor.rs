@@ -0,0 +1,178 @@
+use crate::{Interpreter, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH};
+
+/// Frames a switched-off pixel takes to fade out unless configured otherwise.
+pub const DEFAULT_DECAY_FRAMES: u8 = 8;
+/// Intensity of a lit pixel.
+pub const FULL_INTENSITY: u8 = 255;
+const ALL_ROWS: u64 = u64::MAX;
+
+/// Turns the framebuffer into per-pixel intensity, the way a CRT phosphor keeps
+/// glowing for a moment after the beam moves on. Sprites that are XOR-erased and
+/// redrawn every frame then stay visible instead of flickering.
+///
+/// While disabled it passes the framebuffer through unchanged, so a frontend can
+/// always draw through it.
+#[derive(Debug, Clone)]
+pub struct Phosphor {
+    enabled: bool,
+    decay_frames: u8,
+    width: usize,
+    height: usize,
+    colors: [[u8; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT],
+    levels: [[u8; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT],
+    fading_rows: u64,
+    /// Rows to revisit on the next update whether or not they changed.
+    pending_rows: u64,
+}
+
+impl Phosphor {
+    pub fn new(enabled: bool, decay_frames: u8) -> Phosphor {
+        Phosphor {
+            enabled,
+            decay_frames,
+            width: 0,
+            height: 0,
+            colors: [[0; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT],
+            levels: [[0; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT],
+            fading_rows: 0,
+            pending_rows: ALL_ROWS,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.pending_rows = ALL_ROWS;
+    }
+
+    pub fn decay_frames(&self) -> u8 {
+        self.decay_frames
+    }
+
+    /// How many frames a pixel takes to go dark once switched off; 0 is instant.
+    pub fn set_decay_frames(&mut self, frames: u8) {
+        self.decay_frames = frames;
+        self.pending_rows = ALL_ROWS;
+    }
+
+    /// Makes the next update report every row, e.g. after the palette changed.
+    pub fn invalidate(&mut self) {
+        self.pending_rows = ALL_ROWS;
+    }
+
+    /// Advances one displayed frame and returns the rows, row 0 in bit 0, whose
+    /// colour or intensity changed and need redrawing.
+    pub fn update(&mut self, interpreter: &mut Interpreter) -> u64 {
+        let width = interpreter.screen_width();
+        let height = interpreter.screen_height();
+        let mut rows = interpreter.take_dirty_rows() | self.pending_rows;
+        self.pending_rows = 0;
+        self.height = height;
+        if width != self.width {
+            // nothing lingers across a resolution change
+            self.width = width;
+            self.levels = [[0; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT];
+            rows = ALL_ROWS;
+        }
+
+        let step = match self.decay_frames {
+            frames if self.enabled && frames > 0 => FULL_INTENSITY.div_ceil(frames),
+            _ => FULL_INTENSITY,
+        };
+        let framebuffer = interpreter.framebuffer();
+        let mut changed = rows;
+        let mut fading = 0;
+        for row in (0..height).filter(|row| (rows | self.fading_rows) & (1 << row) != 0) {
+            let bit = 1 << row;
+            let pixels = framebuffer.row_pixels(row, width);
+            let cells = self.colors[row].iter_mut().zip(self.levels[row].iter_mut());
+            for (pixel, (color, level)) in pixels.zip(cells) {
+                let (new_color, new_level) = if pixel != 0 {
+                    (pixel, FULL_INTENSITY)
+                } else {
+                    let faded = level.saturating_sub(step);
+                    (if faded == 0 { 0 } else { *color }, faded)
+                };
+                if (new_color, new_level) != (*color, *level) {
+                    changed |= bit;
+                }
+                if new_level > 0 && pixel == 0 {
+                    fading |= bit;
+                }
+                (*color, *level) = (new_color, new_level);
+            }
+        }
+        self.fading_rows = fading;
+        changed
+    }
+
+    /// Width and height of the display as of the last update.
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// The colour index a pixel last had and how brightly it still shows it, from 0
+    /// to `FULL_INTENSITY`. Dark pixels have colour 0.
+    pub fn pixel(&self, x: usize, y: usize) -> (u8, u8) {
+        (self.colors[y][x], self.levels[y][x])
+    }
+
+    /// The pixels of the first `width` columns of `row`, as from `pixel`.
+    pub fn row_pixels(&self, row: usize, width: usize) -> impl Iterator<Item = (u8, u8)> + '_ {
+        (0..width).map(move |x| self.pixel(x, row))
+    }
+}
+
+impl Default for Phosphor {
+    fn default() -> Self {
+        Phosphor::new(false, DEFAULT_DECAY_FRAMES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interpreter() -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        // draw the font sprite for 0 at (0, 0), then erase it
+        interpreter.load(&[0xD0, 0x05, 0xD0, 0x05]).unwrap();
+        interpreter
+    }
+
+    #[test]
+    fn test_disabled_passes_through() {
+        let mut interpreter = interpreter();
+        let mut phosphor = Phosphor::new(false, 4);
+        interpreter.tick().unwrap();
+        phosphor.update(&mut interpreter);
+        assert_eq!(phosphor.pixel(0, 0), (1, FULL_INTENSITY));
+
+        interpreter.tick().unwrap();
+        assert_eq!(phosphor.update(&mut interpreter) & 0b11111, 0b11111);
+        assert_eq!(phosphor.pixel(0, 0).1, 0);
+    }
+
+    #[test]
+    fn test_switched_off_pixels_fade() {
+        let mut interpreter = interpreter();
+        let mut phosphor = Phosphor::new(true, 4);
+        interpreter.tick().unwrap();
+        phosphor.update(&mut interpreter);
+        interpreter.tick().unwrap();
+
+        let mut levels = Vec::new();
+        for _ in 0..5 {
+            let rows = phosphor.update(&mut interpreter);
+            let (color, level) = phosphor.pixel(0, 0);
+            assert_eq!(color, (level > 0) as u8);
+            levels.push(level);
+            assert_eq!(rows & 1 != 0, levels.len() <= 4);
+        }
+        assert_eq!(levels, [191, 127, 63, 0, 0]);
+        assert_eq!(phosphor.pixel(0, 0), (0, 0));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{render::RGBA_BYTES, Interpreter, Palette};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+/// Encodes an 8-bit PNG with uncompressed deflate blocks. `pixels` holds the rows
+/// top first with `channels` bytes per pixel: 1 for grayscale, 3 for RGB or 4 for
+/// RGBA.
+///
+/// Panics on any other channel count or if `pixels` is too short.
+pub fn encode_png(pixels: &[u8], width: usize, height: usize, channels: usize) -> Vec<u8> {
+    let color_type = match channels {
+        1 => 0,
+        3 => 2,
+        4 => 6,
+        _ => panic!("PNG pixels need 1, 3 or 4 channels, not {channels}"),
+    };
+    let stride = width * channels;
+    assert!(pixels.len() >= stride * height, "PNG pixels too short");
+
+    let mut raw = Vec::with_capacity((stride + 1) * height);
+    for row in pixels.chunks_exact(stride).take(height) {
+        // filter type: none
+        raw.push(0);
+        raw.extend(row);
+    }
+
+    let mut header = Vec::new();
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // bit depth 8, deflate, no filter, no interlace
+    header.extend([8, color_type, 0, 0, 0]);
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+impl Interpreter {
+    /// The display as an RGBA PNG in `palette`, `scale` image pixels per CHIP-8 pixel.
+    ///
+    /// Panics if `scale` is 0.
+    pub fn screenshot_png(&self, palette: &Palette, scale: usize) -> Vec<u8> {
+        let width = self.screen_width() * scale;
+        let height = self.screen_height() * scale;
+        let mut pixels = vec![0; width * height * RGBA_BYTES];
+        self.render_rgba(palette, scale, &mut pixels);
+        encode_png(&pixels, width, height, RGBA_BYTES)
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        out.push(last as u8);
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_screenshot_layout() {
+        let mut interpreter = Interpreter::new();
+        // draw the font sprite for 0 at (0, 0)
+        interpreter.load(&[0xD0, 0x05]).unwrap();
+        interpreter.tick().unwrap();
+
+        let png = interpreter.screenshot_png(&Palette::CLASSIC, 2);
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 128);
+        assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), 64);
+        // bit depth 8, RGBA
+        assert_eq!(png[24..26], [8, 6]);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+        // the first stored block starts with the first filtered row
+        let idat = &png[33..];
+        assert_eq!(&idat[4..8], b"IDAT");
+        assert_eq!(idat[15..20], [0, 255, 255, 255, 255]);
+    }
+}
@@ -0,0 +1,101 @@
+/// How `FX55`/`FX65` leave the index register once the registers have been transferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexIncrement {
+    /// I is left untouched (SUPER-CHIP 1.1).
+    Unchanged,
+    /// I is incremented by X (CHIP-48).
+    ByX,
+    /// I is incremented by X + 1 (COSMAC VIP, XO-CHIP).
+    ByXPlusOne,
+}
+
+/// Behaviour of the opcodes that differ between CHIP-8 implementations.
+///
+/// `Quirks::default()` matches what this interpreter has always done; the named
+/// presets match the platforms ROMs are usually written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` copy VY into VX before shifting instead of shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// What `FX55`/`FX65` do to I.
+    pub index_increment: IndexIncrement,
+    /// `BNNN` jumps to XNN + VX instead of NNN + V0.
+    pub jump_uses_vx: bool,
+    /// `8XY1`/`8XY2`/`8XY3` reset VF to 0.
+    pub logic_resets_vf: bool,
+    /// `DXYN` clips sprites at the screen edges instead of wrapping them around.
+    pub clip_sprites: bool,
+    /// `DXYN` waits for the vertical blank, so at most one sprite is drawn per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    pub const fn vip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            index_increment: IndexIncrement::ByXPlusOne,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            clip_sprites: true,
+            display_wait: true,
+        }
+    }
+
+    pub const fn chip48() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            index_increment: IndexIncrement::ByX,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    pub const fn schip() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            index_increment: IndexIncrement::Unchanged,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    pub const fn xochip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            index_increment: IndexIncrement::ByXPlusOne,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+
+    /// Looks up a preset by name: `default`, `vip`, `chip48`, `schip` or `xochip`.
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Some(Quirks::default()),
+            "vip" | "chip8" => Some(Quirks::vip()),
+            "chip48" => Some(Quirks::chip48()),
+            "schip" | "superchip" => Some(Quirks::schip()),
+            "xochip" | "xo-chip" => Some(Quirks::xochip()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            index_increment: IndexIncrement::Unchanged,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+pub const DEFAULT_SEED: u64 = 0x5EED_C8C8_5EED_C8C8;
+
+/// Source of the bytes returned by `CXNN`.
+///
+/// The whole state of a source must fit in a `u64` so that it can be captured
+/// alongside the rest of the machine and restored later.
+pub trait RandomSource: fmt::Debug {
+    fn next_byte(&mut self) -> u8;
+
+    fn reseed(&mut self, seed: u64);
+
+    fn state(&self) -> u64;
+
+    fn set_state(&mut self, state: u64);
+
+    fn boxed_clone(&self) -> Box<dyn RandomSource>;
+}
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+/// The default generator: SplitMix64, which is fast, small and good enough for games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Default for SplitMix64 {
+    fn default() -> Self {
+        SplitMix64::new(DEFAULT_SEED)
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.state = seed;
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+
+    fn boxed_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = SplitMix64::new(7);
+        let mut b = SplitMix64::new(7);
+        for _ in 0..100 {
+            assert_eq!(a.next_byte(), b.next_byte());
+        }
+    }
+
+    #[test]
+    fn test_restore_state() {
+        let mut rng = SplitMix64::new(7);
+        rng.next_byte();
+        let state = rng.state();
+        let expected: Vec<u8> = (0..10).map(|_| rng.next_byte()).collect();
+
+        rng.set_state(state);
+        let replayed: Vec<u8> = (0..10).map(|_| rng.next_byte()).collect();
+        assert_eq!(expected, replayed);
+    }
+
+    #[test]
+    fn test_boxed_clone_keeps_state() {
+        let mut rng: Box<dyn RandomSource> = Box::new(SplitMix64::new(7));
+        rng.next_byte();
+        let mut copy = rng.clone();
+        assert_eq!(rng.next_byte(), copy.next_byte());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{Interpreter, Palette, Phosphor, Rgb};
+
+/// Bytes per pixel of the images `render_rgba` writes.
+pub const RGBA_BYTES: usize = 4;
+const ALL_ROWS: u64 = u64::MAX;
+
+/// Length of an RGBA8 image of a `width` x `height` display at `scale`.
+pub fn rgba_len(width: usize, height: usize, scale: usize) -> usize {
+    width * scale * height * scale * RGBA_BYTES
+}
+
+/// Writes the selected rows of a `width` x `height` display into `out`, an RGBA8
+/// image `width * scale` pixels wide, with `pixel` giving the colour at (x, y).
+fn render_rows(
+    width: usize,
+    height: usize,
+    scale: usize,
+    rows: u64,
+    out: &mut [u8],
+    pixel: impl Fn(usize, usize) -> Rgb,
+) {
+    assert!(scale > 0, "RGBA scale must be at least 1");
+    assert!(
+        out.len() >= rgba_len(width, height, scale),
+        "RGBA buffer too small for a {width}x{height} display at scale {scale}"
+    );
+    let stride = width * scale * RGBA_BYTES;
+    for row in (0..height).filter(|row| rows & (1 << row) != 0) {
+        let lines = &mut out[row * scale * stride..][..scale * stride];
+        let (first, rest) = lines.split_at_mut(stride);
+        for (x, block) in first.chunks_exact_mut(scale * RGBA_BYTES).enumerate() {
+            let [r, g, b] = pixel(x, row);
+            for rgba in block.chunks_exact_mut(RGBA_BYTES) {
+                rgba.copy_from_slice(&[r, g, b, 0xFF]);
+            }
+        }
+        for line in rest.chunks_exact_mut(stride) {
+            line.copy_from_slice(first);
+        }
+    }
+}
+
+impl Interpreter {
+    /// Renders the display in `palette` into `out` as RGBA8, `screen_width() * scale`
+    /// by `screen_height() * scale` pixels, top row first.
+    ///
+    /// Panics if `scale` is 0 or `out` is shorter than
+    /// `rgba_len(screen_width(), screen_height(), scale)`.
+    pub fn render_rgba(&self, palette: &Palette, scale: usize, out: &mut [u8]) {
+        let width = self.screen_width();
+        let height = self.screen_height();
+        render_rows(width, height, scale, ALL_ROWS, out, |x, y| {
+            palette.color(self.framebuffer.pixel(x, y))
+        });
+    }
+}
+
+impl Phosphor {
+    /// Like `Interpreter::render_rgba`, with faded pixels mixed into the background,
+    /// for the display as of the last `update`. Only the selected `rows` are written,
+    /// so passing what `update` returned keeps the rest of `out` from earlier frames.
+    pub fn render_rgba(&self, palette: &Palette, scale: usize, rows: u64, out: &mut [u8]) {
+        let (width, height) = self.size();
+        render_rows(width, height, scale, rows, out, |x, y| {
+            let (pixel, level) = self.pixel(x, y);
+            palette.shade(pixel, level)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "scale must be at least 1")]
+    fn test_render_rejects_zero_scale() {
+        Interpreter::new().render_rgba(&Palette::CLASSIC, 0, &mut []);
+    }
+
+    #[test]
+    fn test_render_scales_pixels() {
+        let mut interpreter = Interpreter::new();
+        // the top row of the font sprite for 0 is 0xF0
+        interpreter.load(&[0xD0, 0x01]).unwrap();
+        interpreter.tick().unwrap();
+
+        let scale = 2;
+        let mut out = vec![0; rgba_len(64, 32, scale)];
+        interpreter.render_rgba(&Palette::CLASSIC, scale, &mut out);
+        let at = |x: usize, y: usize| &out[(y * 64 * scale + x) * RGBA_BYTES..][..RGBA_BYTES];
+        assert_eq!(at(0, 0), [255, 255, 255, 255]);
+        assert_eq!(at(7, 1), [255, 255, 255, 255]);
+        assert_eq!(at(8, 0), [0, 0, 0, 255]);
+        assert_eq!(at(0, 2), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_phosphor_renders_selected_rows() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0xD0, 0x02]).unwrap();
+        interpreter.tick().unwrap();
+        let mut phosphor = Phosphor::new(false, 0);
+        phosphor.update(&mut interpreter);
+
+        let mut out = vec![0; rgba_len(64, 32, 1)];
+        phosphor.render_rgba(&Palette::CLASSIC, 1, 1 << 1, &mut out);
+        assert_eq!(out[..RGBA_BYTES], [0, 0, 0, 0]);
+        assert_eq!(out[64 * RGBA_BYTES..][..RGBA_BYTES], [255, 255, 255, 255]);
+    }
+}
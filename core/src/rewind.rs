@@ -0,0 +1,270 @@
+use std::collections::VecDeque;
+
+use crate::Interpreter;
+
+const FULL: u8 = 0;
+const XOR_RLE: u8 = 1;
+
+/// Rewind history: a bounded ring of save states, taken every `interval` frames.
+///
+/// Only the newest snapshot is kept whole. Every older one is stored as the
+/// run-length encoded XOR against its newer neighbour, which is tiny because little
+/// of the machine changes from one frame to the next.
+#[derive(Debug, Clone)]
+pub struct Rewind {
+    interval: usize,
+    capacity: usize,
+    frames_since_snapshot: usize,
+    latest: Option<Vec<u8>>,
+    history: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    /// `capacity` is the number of snapshots kept, so the history covers
+    /// `interval * capacity` frames.
+    pub fn new(interval: usize, capacity: usize) -> Rewind {
+        Rewind {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            frames_since_snapshot: 0,
+            latest: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Call once per emulated frame.
+    pub fn record(&mut self, interpreter: &Interpreter) {
+        self.frames_since_snapshot += 1;
+        if self.latest.is_some() && self.frames_since_snapshot < self.interval {
+            return;
+        }
+        self.frames_since_snapshot = 0;
+
+        let state = interpreter.save_state();
+        if let Some(previous) = self.latest.replace(state) {
+            let delta = encode_delta(self.latest.as_ref().unwrap(), &previous);
+            self.history.push_back(delta);
+            if self.history.len() >= self.capacity {
+                self.history.pop_front();
+            }
+        }
+    }
+
+    /// Moves the interpreter back by one snapshot. Returns `false` once the history is
+    /// used up.
+    pub fn step_back(&mut self, interpreter: &mut Interpreter) -> bool {
+        let Some(latest) = self.latest.as_ref() else {
+            return false;
+        };
+
+        if self.frames_since_snapshot > 0 {
+            // the newest snapshot is already behind the running machine
+            self.frames_since_snapshot = 0;
+            return interpreter.load_state(latest).is_ok();
+        }
+
+        let Some(delta) = self.history.pop_back() else {
+            return false;
+        };
+        let previous = decode_delta(latest, &delta);
+        let restored = interpreter.load_state(&previous).is_ok();
+        self.latest = Some(previous);
+        restored
+    }
+
+    /// Number of snapshots that `step_back` can still return to.
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames_since_snapshot = 0;
+        self.latest = None;
+        self.history.clear();
+    }
+
+    /// Bytes held by the history, for display in a frontend.
+    pub fn memory_usage(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.history.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+/// Encodes `target` relative to `base` as alternating (zero run, literal run) pairs
+/// over `base ^ target`.
+fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    if base.len() != target.len() {
+        let mut delta = vec![FULL];
+        delta.extend_from_slice(target);
+        return delta;
+    }
+
+    let mut delta = vec![XOR_RLE];
+    let mut i = 0;
+    while i < target.len() {
+        let zeros_start = i;
+        while i < target.len() && base[i] == target[i] {
+            i += 1;
+        }
+        let literal_start = i;
+        while i < target.len() && base[i] != target[i] {
+            i += 1;
+        }
+        write_varint(&mut delta, literal_start - zeros_start);
+        write_varint(&mut delta, i - literal_start);
+        delta.extend(
+            base[literal_start..i]
+                .iter()
+                .zip(&target[literal_start..i])
+                .map(|(a, b)| a ^ b),
+        );
+    }
+    delta
+}
+
+fn decode_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let (&kind, mut rest) = delta.split_first().expect("empty rewind delta");
+    if kind == FULL {
+        return rest.to_vec();
+    }
+
+    let mut target = base.to_vec();
+    let mut i = 0;
+    while !rest.is_empty() {
+        let zeros = read_varint(&mut rest);
+        let literals = read_varint(&mut rest);
+        i += zeros;
+        for (byte, xor) in target[i..i + literals].iter_mut().zip(rest) {
+            *byte ^= xor;
+        }
+        rest = &rest[literals..];
+        i += literals;
+    }
+    target
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = data.split_first() {
+        *data = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counting_interpreter() -> Interpreter {
+        // V0 += 1, forever
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        interpreter
+    }
+
+    fn run_frame(interpreter: &mut Interpreter) {
+        interpreter.tick().unwrap();
+        interpreter.tick().unwrap();
+    }
+
+    fn v0(interpreter: &Interpreter) -> u8 {
+        interpreter.registers[0]
+    }
+
+    #[test]
+    fn test_delta_round_trip() {
+        let base = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let target = vec![0, 1, 9, 3, 4, 5, 6, 0, 0, 9];
+        let delta = encode_delta(&base, &target);
+        assert_eq!(decode_delta(&base, &delta), target);
+
+        let resized = vec![1, 2, 3];
+        assert_eq!(decode_delta(&base, &encode_delta(&base, &resized)), resized);
+    }
+
+    #[test]
+    fn test_delta_is_compact() {
+        let mut interpreter = counting_interpreter();
+        let before = interpreter.save_state();
+        run_frame(&mut interpreter);
+        let after = interpreter.save_state();
+        assert!(encode_delta(&after, &before).len() < 32);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut interpreter = counting_interpreter();
+        let mut rewind = Rewind::new(1, 100);
+        for _ in 0..5 {
+            run_frame(&mut interpreter);
+            rewind.record(&interpreter);
+        }
+        assert_eq!(v0(&interpreter), 5);
+
+        assert!(rewind.step_back(&mut interpreter));
+        assert_eq!(v0(&interpreter), 4);
+        assert!(rewind.step_back(&mut interpreter));
+        assert_eq!(v0(&interpreter), 3);
+
+        run_frame(&mut interpreter);
+        rewind.record(&interpreter);
+        assert!(rewind.step_back(&mut interpreter));
+        assert_eq!(v0(&interpreter), 3);
+        assert!(rewind.step_back(&mut interpreter));
+        assert!(rewind.step_back(&mut interpreter));
+        assert_eq!(v0(&interpreter), 1);
+        assert!(!rewind.step_back(&mut interpreter));
+        assert_eq!(v0(&interpreter), 1);
+    }
+
+    #[test]
+    fn test_interval() {
+        let mut interpreter = counting_interpreter();
+        let mut rewind = Rewind::new(3, 100);
+        for _ in 0..8 {
+            run_frame(&mut interpreter);
+            rewind.record(&interpreter);
+        }
+        // snapshots after frames 1, 4 and 7
+        assert_eq!(rewind.len(), 2);
+
+        assert!(rewind.step_back(&mut interpreter));
+        assert_eq!(v0(&interpreter), 7);
+        assert!(rewind.step_back(&mut interpreter));
+        assert_eq!(v0(&interpreter), 4);
+        assert!(rewind.step_back(&mut interpreter));
+        assert_eq!(v0(&interpreter), 1);
+        assert!(!rewind.step_back(&mut interpreter));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut interpreter = counting_interpreter();
+        let mut rewind = Rewind::new(1, 4);
+        for _ in 0..10 {
+            run_frame(&mut interpreter);
+            rewind.record(&interpreter);
+        }
+        assert_eq!(rewind.len(), 3);
+
+        while rewind.step_back(&mut interpreter) {}
+        assert_eq!(v0(&interpreter), 7);
+    }
+}
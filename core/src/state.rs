@@ -0,0 +1,331 @@
+use crate::{
+    audio::PATTERN_SIZE, Interpreter, StateError, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH,
+    MEMORY_SIZE, NUMBER_OF_FLAGS, NUMBER_OF_KEYS, NUMBER_OF_REGISTERS, STACK_SIZE, XO_MEMORY_SIZE,
+};
+
+const MAGIC: &[u8; 4] = b"C8SS";
+pub const STATE_VERSION: u16 = 3;
+
+pub(crate) struct StateWriter {
+    pub(crate) data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+}
+
+pub(crate) struct StateReader<'a> {
+    pub(crate) data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Corrupt),
+        }
+    }
+}
+
+impl Interpreter {
+    /// Serialises the whole machine: memory, registers, stack, timers, screen, keys,
+    /// audio pattern, VIP cycle balance and random state. The result can only be
+    /// restored while the same ROM is loaded.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter {
+            data: Vec::with_capacity(
+                self.memory.len() + HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT + 128,
+            ),
+        };
+
+        writer.bytes(MAGIC);
+        writer.u16(STATE_VERSION);
+        writer.u64(self.rom_hash);
+
+        writer.u16(self.program_counter);
+        writer.u16(self.index_register);
+        writer.u8(self.stack_pointer);
+        for address in self.stack {
+            writer.u16(address);
+        }
+        writer.bytes(&self.registers);
+        writer.u8(self.delay_timer);
+        writer.u8(self.sound_timer);
+        writer.u16(self.keys_mask());
+        writer.bytes(&self.flags);
+        writer.u64(self.seed);
+        writer.u64(self.rng.state());
+        writer.bool(self.voice.pattern.is_some());
+        writer.bytes(&self.voice.pattern.unwrap_or_default());
+        writer.u8(self.voice.pitch);
+        writer.u32(self.cycles_left as u32);
+
+        writer.bool(self.xo_chip);
+        writer.bool(self.hires);
+        writer.bool(self.halted);
+        writer.u8(self.planes);
+        for row in self.get_screen() {
+            writer.bytes(&row);
+        }
+
+        writer.u32(self.memory.len() as u32);
+        writer.bytes(&self.memory);
+
+        writer.data
+    }
+
+    /// Restores a state produced by `save_state`. Nothing is changed if the state is
+    /// rejected.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut reader = StateReader { data };
+
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(StateError::InvalidMagic);
+        }
+        let version = reader.u16()?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        let rom_hash = reader.u64()?;
+        if rom_hash != self.rom_hash {
+            return Err(StateError::RomMismatch {
+                expected: self.rom_hash,
+                found: rom_hash,
+            });
+        }
+
+        let mut restored = self.clone();
+        restored.program_counter = reader.u16()?;
+        restored.index_register = reader.u16()?;
+        restored.stack_pointer = reader.u8()?;
+        if restored.stack_pointer as usize > STACK_SIZE {
+            return Err(StateError::Corrupt);
+        }
+        for address in restored.stack.iter_mut() {
+            *address = reader.u16()?;
+        }
+        restored.registers = reader.array::<NUMBER_OF_REGISTERS>()?;
+        restored.delay_timer = reader.u8()?;
+        restored.sound_timer = reader.u8()?;
+        restored.set_keys_mask(reader.u16()?);
+        restored.flags = reader.array::<NUMBER_OF_FLAGS>()?;
+        restored.seed = reader.u64()?;
+        restored.rng.set_state(reader.u64()?);
+        let has_pattern = reader.bool()?;
+        let pattern = reader.array::<PATTERN_SIZE>()?;
+        restored.voice.pattern = has_pattern.then_some(pattern);
+        restored.voice.pitch = reader.u8()?;
+        restored.cycles_left = reader.u32()? as i32;
+
+        restored.xo_chip = reader.bool()?;
+        restored.hires = reader.bool()?;
+        restored.halted = reader.bool()?;
+        restored.planes = reader.u8()?;
+        for y in 0..HIRES_SCREEN_HEIGHT {
+            for (x, &pixel) in reader.bytes(HIRES_SCREEN_WIDTH)?.iter().enumerate() {
+                restored.framebuffer.set_pixel(x, y, pixel);
+            }
+        }
+        restored.framebuffer.mark_all_changed();
+
+        let memory_size = reader.u32()? as usize;
+        let expected_size = if restored.xo_chip {
+            XO_MEMORY_SIZE
+        } else {
+            MEMORY_SIZE
+        };
+        if memory_size != expected_size {
+            return Err(StateError::Corrupt);
+        }
+        restored.memory = reader.bytes(memory_size)?.to_vec();
+
+        if !reader.data.is_empty() {
+            return Err(StateError::Corrupt);
+        }
+
+        restored.tracer = std::mem::take(&mut self.tracer);
+        *self = restored;
+        Ok(())
+    }
+
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    pub(crate) fn keys_mask(&self) -> u16 {
+        self.keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (key, &pressed)| mask | (pressed as u16) << key)
+    }
+
+    pub(crate) fn set_keys_mask(&mut self, mask: u16) {
+        for key in 0..NUMBER_OF_KEYS {
+            self.keys[key] = mask & (1 << key) != 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Interpreter, StateError};
+
+    fn running_interpreter() -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(7);
+        // draw the font sprite for V0, call a subroutine and roll a random number
+        interpreter
+            .load(&[
+                0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0x22, 0x0A, 0x12, 0x08, 0xC1, 0xFF, 0x00, 0xEE,
+            ])
+            .unwrap();
+        for _ in 0..6 {
+            interpreter.tick().unwrap();
+        }
+        interpreter.keypress(3, true);
+        interpreter
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut interpreter = running_interpreter();
+        let state = interpreter.save_state();
+        let expected = interpreter.clone();
+
+        interpreter.tick().unwrap();
+        interpreter.tick().unwrap();
+        interpreter.keypress(3, false);
+        interpreter.load_state(&state).unwrap();
+
+        assert_eq!(interpreter.save_state(), state);
+        assert_eq!(interpreter.program_counter, expected.program_counter);
+        assert_eq!(interpreter.get_screen(), expected.get_screen());
+        assert!(interpreter.keys[3]);
+
+        let mut replayed = expected.clone();
+        interpreter.execute(0xC2FF).unwrap();
+        replayed.execute(0xC2FF).unwrap();
+        assert_eq!(interpreter.registers[2], replayed.registers[2]);
+    }
+
+    #[test]
+    fn test_round_trip_xo_chip() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_xo_chip(true);
+        interpreter.load(&[0x00, 0xFF]).unwrap();
+        interpreter.tick().unwrap();
+        interpreter.memory[0xFFFF] = 0x42;
+        interpreter.voice.pattern = Some([0x0F; 16]);
+        interpreter.voice.pitch = 100;
+        let state = interpreter.save_state();
+
+        let mut restored = Interpreter::new();
+        restored.load(&[0x00, 0xFF]).unwrap();
+        restored.load_state(&state).unwrap();
+        assert!(restored.is_xo_chip());
+        assert!(restored.is_hires());
+        assert_eq!(restored.memory[0xFFFF], 0x42);
+        assert_eq!(restored.audio_pattern(), Some(&[0x0F; 16]));
+        assert_eq!(restored.pitch(), 100);
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        let mut interpreter = running_interpreter();
+        let mut state = interpreter.save_state();
+        state[0] = b'X';
+        assert_eq!(
+            interpreter.load_state(&state),
+            Err(StateError::InvalidMagic)
+        );
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut interpreter = running_interpreter();
+        let mut state = interpreter.save_state();
+        state[4..6].copy_from_slice(&99u16.to_be_bytes());
+        assert_eq!(
+            interpreter.load_state(&state),
+            Err(StateError::UnsupportedVersion(99))
+        );
+    }
+
+    #[test]
+    fn test_rom_mismatch() {
+        let state = running_interpreter().save_state();
+        let mut other = Interpreter::new();
+        other.load(&[0x12, 0x00]).unwrap();
+        let before = other.save_state();
+
+        assert!(matches!(
+            other.load_state(&state),
+            Err(StateError::RomMismatch { .. })
+        ));
+        assert_eq!(other.save_state(), before);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut interpreter = running_interpreter();
+        let state = interpreter.save_state();
+        assert_eq!(
+            interpreter.load_state(&state[..state.len() - 1]),
+            Err(StateError::Truncated)
+        );
+    }
+}
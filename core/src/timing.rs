@@ -0,0 +1,139 @@
+use crate::{Instruction, Interpreter};
+
+/// The VIP's 1.76 MHz clock divided into 8-clock machine cycles, per 60 Hz frame.
+const FRAME_CYCLES: i32 = 3668;
+/// Cycles the display takes each frame to fetch 128 lines of 8 bytes by DMA.
+const DISPLAY_CYCLES: i32 = 1024;
+/// Cycles left for the interpreter in each frame.
+pub(crate) const VIP_CYCLES_PER_FRAME: i32 = FRAME_CYCLES - DISPLAY_CYCLES;
+/// The interpreter's fetch and dispatch, paid by every instruction.
+const FETCH_CYCLES: u32 = 40;
+/// Extra cost of a conditional skip that is taken.
+const SKIP_CYCLES: u32 = 4;
+
+/// How fast the interpreter runs each 60 Hz frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timing {
+    /// A fixed number of instructions per frame, set with
+    /// `Interpreter::set_ticks_per_frame`.
+    #[default]
+    Uniform,
+    /// Each instruction costs the machine cycles it takes on a COSMAC VIP, out of a
+    /// fixed budget per frame. Overspent cycles carry over to the next frame.
+    Vip,
+}
+
+impl Timing {
+    /// Looks up a mode by name: `uniform` or `vip`.
+    pub fn from_name(name: &str) -> Option<Timing> {
+        match name.to_ascii_lowercase().as_str() {
+            "uniform" => Some(Timing::Uniform),
+            "vip" | "cosmac" => Some(Timing::Vip),
+            _ => None,
+        }
+    }
+}
+
+/// Machine cycles `instruction` takes on a VIP, from the state before it runs.
+/// Instructions the VIP does not have only pay for the fetch.
+pub(crate) fn vip_cycles(instruction: Instruction, interpreter: &Interpreter) -> u32 {
+    let vx = |x: u8| interpreter.registers[x as usize] as u32;
+    let execute = match instruction {
+        Instruction::Cls => 3078,
+        Instruction::Ret => 10,
+        Instruction::Jp(_) | Instruction::LdI(_) => 12,
+        Instruction::Call(_) => 26,
+        Instruction::SeByte { .. } | Instruction::SneByte { .. } => 10,
+        Instruction::SeReg { .. } | Instruction::SneReg { .. } => 14,
+        Instruction::LdByte { .. } => 6,
+        Instruction::AddByte { .. } => 10,
+        Instruction::LdReg { .. }
+        | Instruction::Or { .. }
+        | Instruction::And { .. }
+        | Instruction::Xor { .. }
+        | Instruction::AddReg { .. }
+        | Instruction::Sub { .. }
+        | Instruction::Shr { .. }
+        | Instruction::Subn { .. }
+        | Instruction::Shl { .. } => 44,
+        // a target on another page than NNN costs another pass through the carry
+        Instruction::JpV0(addr) => {
+            if (addr + vx(0) as u16) & 0xF00 == addr & 0xF00 {
+                22
+            } else {
+                24
+            }
+        }
+        Instruction::Rnd { .. } => 36,
+        Instruction::Drw { x, n, .. } => draw_cycles(vx(x), n),
+        Instruction::Skp(_) | Instruction::Sknp(_) => 14,
+        Instruction::LdVxDt(_)
+        | Instruction::LdVxK(_)
+        | Instruction::LdDtVx(_)
+        | Instruction::LdStVx(_) => 10,
+        Instruction::AddI(_) | Instruction::LdF(_) => 16,
+        // the digits are found by repeated subtraction
+        Instruction::LdB(x) => {
+            let value = vx(x);
+            80 + 16 * (value / 100 + value / 10 % 10 + value % 10)
+        }
+        Instruction::LdMem(x) | Instruction::LdRegs(x) => 14 + 14 * (x as u32 + 1),
+        _ => 0,
+    };
+    FETCH_CYCLES + execute
+}
+
+/// Sprites at a multiple of 8 are copied a byte at a time; anywhere else every row
+/// is shifted bit by bit and written to two bytes.
+fn draw_cycles(x: u32, n: u8) -> u32 {
+    let rows = if n == 0 { 16 } else { n as u32 };
+    let shift = x % 8;
+    let row = if shift == 0 { 34 } else { 46 + 4 * shift };
+    26 + rows * row
+}
+
+/// Extra cycles when `instruction` skipped the next one.
+pub(crate) fn vip_skip_cycles(instruction: Instruction) -> u32 {
+    match instruction {
+        Instruction::SeByte { .. }
+        | Instruction::SneByte { .. }
+        | Instruction::SeReg { .. }
+        | Instruction::SneReg { .. }
+        | Instruction::Skp(_)
+        | Instruction::Sknp(_) => SKIP_CYCLES,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_depends_on_alignment_and_height() {
+        let interpreter = Interpreter::new();
+        let draw = |n| Instruction::Drw { x: 0, y: 0, n };
+        assert_eq!(vip_cycles(draw(1), &interpreter), 40 + 26 + 34);
+        assert_eq!(vip_cycles(draw(5), &interpreter), 40 + 26 + 5 * 34);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.registers[0] = 3;
+        assert_eq!(vip_cycles(draw(5), &interpreter), 40 + 26 + 5 * 58);
+    }
+
+    #[test]
+    fn test_bcd_depends_on_digits() {
+        let mut interpreter = Interpreter::new();
+        interpreter.registers[1] = 0;
+        assert_eq!(vip_cycles(Instruction::LdB(1), &interpreter), 120);
+        interpreter.registers[1] = 255;
+        assert_eq!(vip_cycles(Instruction::LdB(1), &interpreter), 120 + 16 * 12);
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Timing::from_name("VIP"), Some(Timing::Vip));
+        assert_eq!(Timing::from_name("uniform"), Some(Timing::Uniform));
+        assert_eq!(Timing::from_name("fast"), None);
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::{Instruction, NUMBER_OF_REGISTERS};
+
+/// The machine state just before an instruction executes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: Option<Instruction>,
+    pub registers: [u8; NUMBER_OF_REGISTERS],
+    pub index_register: u16,
+    pub stack_pointer: u8,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// One line per instruction, fixed width up to the `;` so that traces diff
+/// cleanly, e.g.
+///
+/// `PC=0200 OP=6003 V=00000000000000000000000000000000 I=0000 SP=00 DT=00 ST=00 ; LD V0, 0x03`
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PC={:04X} OP={:04X} V=", self.pc, self.opcode)?;
+        for register in self.registers {
+            write!(f, "{register:02X}")?;
+        }
+        write!(
+            f,
+            " I={:04X} SP={:02X} DT={:02X} ST={:02X} ; ",
+            self.index_register, self.stack_pointer, self.delay_timer, self.sound_timer
+        )?;
+        match self.instruction {
+            Some(instruction) => write!(f, "{instruction}"),
+            None => write!(f, "???"),
+        }
+    }
+}
+
+/// Receives an entry for every instruction the interpreter executes.
+pub trait TraceSink {
+    fn record(&mut self, entry: &TraceEntry);
+}
+
+impl<F: FnMut(&TraceEntry)> TraceSink for F {
+    fn record(&mut self, entry: &TraceEntry) {
+        self(entry)
+    }
+}
+
+/// Writes entries as lines. The trace stops at the first write error.
+pub struct TraceWriter<W: Write> {
+    writer: Option<W>,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(writer: W) -> TraceWriter<W> {
+        TraceWriter {
+            writer: Some(writer),
+        }
+    }
+}
+
+impl TraceWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(TraceWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> TraceSink for TraceWriter<W> {
+    fn record(&mut self, entry: &TraceEntry) {
+        if let Some(writer) = self.writer.as_mut() {
+            if writeln!(writer, "{entry}").is_err() {
+                self.writer = None;
+            }
+        }
+    }
+}
+
+/// The interpreter's sink. Clones and snapshots don't carry it, so only the live
+/// machine writes to a trace.
+#[derive(Default)]
+pub(crate) struct Tracer(pub(crate) Option<Box<dyn TraceSink>>);
+
+impl Clone for Tracer {
+    fn clone(&self) -> Self {
+        Tracer(None)
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() {
+            "Tracer(on)"
+        } else {
+            "Tracer(off)"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interpreter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_line_format() {
+        let mut registers = [0; NUMBER_OF_REGISTERS];
+        registers[0] = 0x12;
+        registers[15] = 0x01;
+        let entry = TraceEntry {
+            pc: 0x200,
+            opcode: 0x6003,
+            instruction: Some(Instruction::LdByte { x: 0, byte: 3 }),
+            registers,
+            index_register: 0x300,
+            stack_pointer: 1,
+            delay_timer: 0x3C,
+            sound_timer: 0,
+        };
+        assert_eq!(
+            entry.to_string(),
+            "PC=0200 OP=6003 V=12000000000000000000000000000001 I=0300 SP=01 DT=3C ST=00 ; LD V0, 0x03"
+        );
+    }
+
+    #[test]
+    fn test_interpreter_records_each_instruction() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .load(&[0x60, 0x03, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x00, 0xFF, 0xFF])
+            .unwrap();
+        interpreter.set_xo_chip(true);
+        let entries = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&entries);
+        interpreter.set_trace_sink(Some(Box::new(move |entry: &TraceEntry| {
+            sink.borrow_mut().push(entry.clone())
+        })));
+
+        for _ in 0..3 {
+            interpreter.tick().unwrap();
+        }
+        let entries = entries.borrow();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].pc, 0x200);
+        assert_eq!(entries[1].registers[0], 3);
+        assert_eq!(entries[1].instruction, Some(Instruction::LdILong(0x1234)));
+        assert_eq!(entries[2].pc, 0x206);
+        assert_eq!(entries[2].index_register, 0x1234);
+
+        // clones don't write to the original's trace
+        let mut clone = interpreter.clone();
+        clone.tick().unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn test_writer() {
+        let mut writer = TraceWriter::new(Vec::new());
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&[0x00, 0xE0, 0xFF, 0xFF]).unwrap();
+        let entry = interpreter.trace_entry();
+        writer.record(&entry);
+        writer.record(&entry);
+        let text = String::from_utf8(writer.writer.unwrap()).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.ends_with("; CLS\n"));
+    }
+}
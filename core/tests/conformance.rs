@@ -0,0 +1,166 @@
+//! Whole-program checks: each ROM under `roms` is assembled, run headlessly under
+//! every quirk profile and its final screen compared with `golden/<rom>.<profile>.txt`.
+//!
+//! The ROMs are written for this repo; they are not the community test ROMs. Most
+//! of them also save what they observed at `RESULTS`, and those bytes are checked
+//! against tables taken from the platform documentation, so the expectations don't
+//! come from this interpreter alone.
+//!
+//! Run with `UPDATE_GOLDEN=1` to rewrite the golden images after an intended change.
+
+use assembler::{assemble, Syntax};
+use core::{FrameStatus, Interpreter, Quirks};
+use std::{env, fs, path::PathBuf};
+
+const PROFILES: [&str; 4] = ["vip", "chip48", "schip", "xochip"];
+const PIXELS: [char; 4] = ['.', '#', 'o', '@'];
+/// Where the ROMs save their results.
+const RESULTS: usize = 0xE00;
+
+/// A key change at the start of a frame.
+struct Key {
+    frame: usize,
+    key: usize,
+    pressed: bool,
+}
+
+fn path(parts: &[&str]) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.extend(parts);
+    path
+}
+
+fn screen(interpreter: &Interpreter) -> String {
+    let width = interpreter.screen_width();
+    (0..interpreter.screen_height())
+        .map(|row| {
+            let mut line: String = interpreter
+                .framebuffer()
+                .row_pixels(row, width)
+                .map(|pixel| PIXELS[pixel as usize & 3])
+                .collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+/// Runs `rom` and returns its final screen and the first `results` bytes it saved.
+fn run(
+    rom: &[u8],
+    profile: &str,
+    frames: usize,
+    keys: &[Key],
+    results: usize,
+) -> (String, Vec<u8>) {
+    let quirks = Quirks::from_name(profile).unwrap();
+    let mut interpreter = Interpreter::with_quirks(quirks);
+    interpreter.set_xo_chip(profile == "xochip");
+    interpreter.load(rom).unwrap();
+
+    for frame in 0..frames {
+        for key in keys.iter().filter(|key| key.frame == frame) {
+            interpreter.keypress(key.key, key.pressed);
+        }
+        if let FrameStatus::Fault(err) = interpreter.run_frame().status {
+            panic!("{profile}: {err} in frame {frame}");
+        }
+    }
+    let saved = interpreter.memory()[RESULTS..RESULTS + results].to_vec();
+    (screen(&interpreter), saved)
+}
+
+/// Runs ROM `name` under every profile. `results` holds the bytes it must save,
+/// in the order of `PROFILES`.
+fn check(name: &str, frames: usize, keys: &[Key], results: [&[u8]; 4]) {
+    let source = fs::read_to_string(path(&["roms", &format!("{name}.8o")])).unwrap();
+    let rom = assemble(&source, Syntax::Octo).unwrap_or_else(|err| panic!("{name}.8o:{err}"));
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut failures = Vec::new();
+    for (profile, expected) in PROFILES.into_iter().zip(results) {
+        let (actual, saved) = run(&rom, profile, frames, keys, expected.len());
+        if saved != expected {
+            failures.push(format!(
+                "{profile}: saved {saved:02X?}, expected {expected:02X?}"
+            ));
+        }
+        let golden = path(&["golden", &format!("{name}.{profile}.txt")]);
+        if update {
+            fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+        let expected =
+            fs::read_to_string(&golden).unwrap_or_else(|err| panic!("{}: {err}", golden.display()));
+        if actual != expected {
+            failures.push(format!("{profile}: expected\n{expected}\nfound\n{actual}"));
+        }
+    }
+    assert!(failures.is_empty(), "{name}\n{}", failures.join("\n"));
+}
+
+#[test]
+fn logo() {
+    check("logo", 20, &[], [&[]; 4]);
+}
+
+#[test]
+fn opcodes() {
+    check("opcodes", 100, &[], [&[0]; 4]);
+}
+
+#[test]
+fn flags() {
+    // no crosses, then VF after 8XYE and 8XY6 with VX != VY and after 8XY1
+    check(
+        "flags",
+        100,
+        &[],
+        [&[0, 1, 0, 0], &[0, 0, 1, 5], &[0, 0, 1, 5], &[0, 1, 0, 5]],
+    );
+}
+
+#[test]
+fn quirks() {
+    // VF reset, index increment, shift source, jump, clipping, display wait, as
+    // documented for each platform
+    check(
+        "quirks",
+        50,
+        &[],
+        [
+            &[1, 2, 1, 0, 1, 1],
+            &[0, 1, 0, 1, 1, 0],
+            &[0, 0, 0, 1, 1, 0],
+            &[0, 2, 1, 0, 0, 0],
+        ],
+    );
+}
+
+#[test]
+fn keypad() {
+    let keys = [
+        Key {
+            frame: 5,
+            key: 0xA,
+            pressed: true,
+        },
+        Key {
+            frame: 10,
+            key: 0xA,
+            pressed: false,
+        },
+        Key {
+            frame: 20,
+            key: 5,
+            pressed: true,
+        },
+    ];
+    check("keypad", 40, &keys, [&[5, 0xA]; 4]);
+}
+
+#[test]
+fn self_check() {
+    check("self_check", 100, &[], [&[0]; 4]);
+}
@@ -0,0 +1,148 @@
+use core::debugger::Debugger;
+use core::gdb::GdbServer;
+use core::Interpreter;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+// 0x200: v0 := 3
+// 0x202: v1 += 1
+// 0x204: i := 0x300
+// 0x206: save v1
+// 0x208: jump 0x202
+const PROGRAM: [u8; 10] = [0x60, 0x03, 0x71, 0x01, 0xA3, 0x00, 0xF1, 0x55, 0x12, 0x02];
+
+struct Session {
+    server: GdbServer,
+    debugger: Debugger,
+    interpreter: Interpreter,
+    client: TcpStream,
+}
+
+impl Session {
+    fn new() -> Session {
+        let server = GdbServer::bind(0).unwrap();
+        let client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        client.set_nonblocking(true).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&PROGRAM).unwrap();
+        Session {
+            server,
+            debugger: Debugger::default(),
+            interpreter,
+            client,
+        }
+    }
+
+    fn poll(&mut self) {
+        self.server
+            .poll(&mut self.debugger, &mut self.interpreter)
+            .unwrap();
+    }
+
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.client, "${data}#{checksum:02x}").unwrap();
+    }
+
+    /// Polls the server until a whole reply packet arrives.
+    fn receive(&mut self) -> String {
+        let mut received = Vec::new();
+        for _ in 0..10_000 {
+            self.poll();
+            let mut buffer = [0; 1024];
+            match self.client.read(&mut buffer) {
+                Ok(len) => received.extend_from_slice(&buffer[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => panic!("{err}"),
+            }
+            let text = String::from_utf8_lossy(&received).into_owned();
+            let text = text.trim_start_matches('+');
+            if let Some(end) = text.find('#') {
+                if text.len() >= end + 3 {
+                    assert!(text.starts_with('$'), "unexpected reply {text}");
+                    return text[1..end].to_string();
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("no reply");
+    }
+
+    fn exchange(&mut self, data: &str) -> String {
+        self.send(data);
+        self.receive()
+    }
+}
+
+#[test]
+fn test_registers_and_memory() {
+    let mut session = Session::new();
+    assert_eq!(session.exchange("?"), "S05");
+    assert!(session.server.is_connected());
+
+    let registers = session.exchange("g");
+    assert_eq!(registers.len(), 46);
+    assert_eq!(&registers[32..40], "00000200");
+
+    assert_eq!(session.exchange("m200,4"), "60037101");
+    assert_eq!(session.exchange("M300,2:beef"), "OK");
+    assert_eq!(session.exchange("m300,2"), "beef");
+    assert_eq!(session.exchange("m10000,1"), "E01");
+
+    let mut registers = "ab".to_string() + &"00".repeat(15);
+    registers += "03000202000000";
+    assert_eq!(session.exchange(&format!("G{registers}")), "OK");
+    assert_eq!(session.interpreter.registers()[0], 0xAB);
+    assert_eq!(session.interpreter.index_register(), 0x300);
+    assert_eq!(session.interpreter.program_counter(), 0x202);
+    assert_eq!(session.exchange("p11"), "0202");
+}
+
+#[test]
+fn test_malformed_packets() {
+    let mut session = Session::new();
+    assert_eq!(session.exchange("mffffffffffffffff,2"), "E01");
+    assert_eq!(session.exchange("m200,ffffffffffffffff"), "E01");
+    assert_eq!(session.exchange("m200"), "E01");
+    assert_eq!(session.exchange("Mffffffffffffffff,2:beef"), "E01");
+    assert_eq!(session.exchange("M300,2:be"), "E01");
+    assert_eq!(session.exchange("M300,2"), "E01");
+    let xml = session.exchange("qXfer:features:read:target.xml:0,ffffffffffffffff");
+    assert!(xml.starts_with('l'));
+
+    // a multi-byte first character is an unknown command, not a crash
+    assert_eq!(session.exchange("\u{e9}m200,4"), "");
+    assert_eq!(session.exchange("m200,2"), "6003");
+}
+
+#[test]
+fn test_step_and_continue() {
+    let mut session = Session::new();
+    assert_eq!(session.exchange("s"), "S05");
+    assert_eq!(session.interpreter.registers()[0], 3);
+
+    assert_eq!(session.exchange("Z0,208,2"), "OK");
+    session.send("c");
+    assert_eq!(session.receive(), "S05");
+    assert_eq!(session.interpreter.program_counter(), 0x208);
+
+    assert_eq!(session.exchange("z0,208,2"), "OK");
+    assert_eq!(session.exchange("Z2,301,1"), "OK");
+    session.send("c");
+    assert_eq!(session.receive(), "T05watch:301;");
+    assert_eq!(session.interpreter.memory()[0x301], 2);
+}
+
+#[test]
+fn test_interrupt_and_detach() {
+    let mut session = Session::new();
+    session.send("c");
+    session.poll();
+    assert!(session.server.is_running());
+    session.client.write_all(&[0x03]).unwrap();
+    assert_eq!(session.receive(), "S02");
+
+    assert_eq!(session.exchange("D"), "OK");
+    session.poll();
+    assert!(!session.server.is_connected());
+}
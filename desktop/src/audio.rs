@@ -0,0 +1,59 @@
+use core::Interpreter;
+use sdl2::{
+    audio::{AudioQueue, AudioSpecDesired},
+    AudioSubsystem,
+};
+
+pub const DEFAULT_VOLUME: f32 = 0.25;
+const SAMPLE_RATE: i32 = 44_100;
+const FRAME_RATE: u32 = 60;
+/// Frames of audio allowed to queue up before new frames are dropped.
+const MAX_QUEUED_FRAMES: u32 = 6;
+
+/// Queues one frame of the interpreter's audio at a time.
+pub struct Speaker {
+    queue: AudioQueue<f32>,
+    samples: Vec<f32>,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Speaker {
+    pub fn open(audio: &AudioSubsystem, volume: f32, muted: bool) -> Result<Speaker, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+        let queue = audio.open_queue::<f32, _>(None, &desired)?;
+        let samples = vec![0.0; queue.spec().freq as usize / FRAME_RATE as usize];
+        queue.resume();
+        Ok(Speaker {
+            queue,
+            samples,
+            volume: volume.clamp(0.0, 1.0),
+            muted,
+        })
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.queue.spec().freq as u32
+    }
+
+    /// Renders and queues the frame that just ran.
+    pub fn play_frame(&mut self, chip8: &mut Interpreter) -> Result<(), String> {
+        let sample_rate = self.sample_rate();
+        chip8.fill_audio(&mut self.samples, sample_rate);
+        let queued_frames =
+            self.queue.size() / (self.samples.len() * size_of::<f32>()).max(1) as u32;
+        if queued_frames >= MAX_QUEUED_FRAMES {
+            return Ok(());
+        }
+
+        let gain = if self.muted { 0.0 } else { self.volume };
+        for sample in self.samples.iter_mut() {
+            *sample *= gain;
+        }
+        self.queue.queue_audio(&self.samples)
+    }
+}
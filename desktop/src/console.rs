@@ -0,0 +1,268 @@
+use core::debugger::{parse_number, Access, Condition, Debugger, Register, Stop};
+use core::{disassembler::disassemble, Interpreter};
+use std::io::{self, Write};
+
+/// Upper bound on the instructions one console command may run.
+const BUDGET: usize = 10_000_000;
+
+const HELP: &str = "\
+c, continue              resume the game
+s, step [n]              execute n instructions
+n, next                  step over a subroutine call
+finish                   run until the current subroutine returns
+u, until <addr>          run until PC reaches addr
+b, break <addr> [if <condition>]
+cond <condition>         stop wherever the condition holds, e.g. `v3 == 2 && [i] != 0`
+d, delete <addr>         remove a breakpoint
+dc <n>                   remove condition n
+w, watch <addr>          stop after a write to addr
+rw, rwatch <addr>        stop after a read of addr
+wr <v0-vf|i>             stop when the register changes
+unwatch <addr|register>  remove a watch
+info                     list breakpoints and watches
+r, regs                  show registers
+x <addr> [len]           dump memory
+dis [addr] [count]       disassemble
+q, quit                  exit the emulator";
+
+pub enum Action {
+    Resume,
+    Paused,
+    Quit,
+}
+
+/// Reads and runs one command from stdin.
+pub fn prompt(debugger: &mut Debugger, interpreter: &mut Interpreter) -> Action {
+    print!("(chip8) ");
+    io::stdout().flush().unwrap();
+
+    let mut line = String::new();
+    match io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => return Action::Resume,
+        Ok(_) => {}
+    }
+    match run_command(debugger, interpreter, line.trim()) {
+        Ok(action) => action,
+        Err(message) => {
+            println!("{message}");
+            Action::Paused
+        }
+    }
+}
+
+pub fn report_pause(stop: Stop, interpreter: &Interpreter) {
+    println!("Paused: {stop}");
+    print_location(interpreter);
+}
+
+fn run_command(
+    debugger: &mut Debugger,
+    interpreter: &mut Interpreter,
+    line: &str,
+) -> Result<Action, String> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Ok(Action::Paused);
+    };
+    let args: Vec<&str> = words.collect();
+
+    match command {
+        "c" | "continue" => return Ok(Action::Resume),
+        "q" | "quit" => return Ok(Action::Quit),
+        "h" | "help" => println!("{HELP}"),
+        "s" | "step" => {
+            let count = args.first().map_or(Ok(1), |arg| number(arg))?;
+            for _ in 0..count {
+                let stop = debugger.step(interpreter);
+                if stop != Stop::Done {
+                    println!("{stop}");
+                    break;
+                }
+            }
+            print_location(interpreter);
+        }
+        "n" | "next" => report_run(debugger.step_over(interpreter, BUDGET), interpreter),
+        "finish" => report_run(debugger.step_out(interpreter, BUDGET), interpreter),
+        "u" | "until" => {
+            let address = number(argument(&args, 0)?)?;
+            report_run(debugger.run_to(interpreter, address, BUDGET), interpreter);
+        }
+        "b" | "break" => {
+            let address = number(argument(&args, 0)?)?;
+            match args.get(1) {
+                Some(&"if") => {
+                    let condition = condition(&args[2..])?;
+                    let index = debugger.add_condition(Some(address), condition);
+                    println!("Condition {index} at {address:04X}");
+                }
+                Some(other) => return Err(format!("expected `if`, found `{other}`")),
+                None => {
+                    debugger.add_breakpoint(address);
+                    println!("Breakpoint at {address:04X}");
+                }
+            }
+        }
+        "cond" => {
+            let index = debugger.add_condition(None, condition(&args)?);
+            println!("Condition {index}");
+        }
+        "d" | "delete" => {
+            let address = number(argument(&args, 0)?)?;
+            if !debugger.remove_breakpoint(address) {
+                return Err(format!("no breakpoint at {address:04X}"));
+            }
+        }
+        "dc" => {
+            let index = number(argument(&args, 0)?)?;
+            if !debugger.remove_condition(index as usize) {
+                return Err(format!("no condition {index}"));
+            }
+        }
+        "w" | "watch" | "rw" | "rwatch" => {
+            let address = number(argument(&args, 0)?)?;
+            let access = if command.starts_with('r') {
+                Access::Read
+            } else {
+                Access::Write
+            };
+            debugger.add_watchpoint(address, access);
+        }
+        "wr" => debugger.watch_register(register(argument(&args, 0)?)?),
+        "unwatch" => {
+            let arg = argument(&args, 0)?;
+            let removed = match register(arg) {
+                Ok(register) => debugger.unwatch_register(register),
+                Err(_) => {
+                    let address = number(arg)?;
+                    let read = debugger.remove_watchpoint(address, Access::Read);
+                    debugger.remove_watchpoint(address, Access::Write) || read
+                }
+            };
+            if !removed {
+                return Err(format!("nothing watched at `{arg}`"));
+            }
+        }
+        "info" => print_info(debugger),
+        "r" | "regs" => print_registers(interpreter),
+        "x" => {
+            let address = number(argument(&args, 0)?)? as usize;
+            let len = args.get(1).map_or(Ok(16), |arg| number(arg))? as usize;
+            let memory = interpreter.memory();
+            let end = (address + len).min(memory.len());
+            for (row, bytes) in memory[address.min(end)..end].chunks(16).enumerate() {
+                let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+                println!("{:04X}  {}", address + row * 16, bytes.join(" "));
+            }
+        }
+        "dis" => {
+            let address = match args.first() {
+                Some(arg) => number(arg)?,
+                None => interpreter.program_counter(),
+            };
+            let count = args.get(1).map_or(Ok(8), |arg| number(arg))? as usize;
+            print_disassembly(interpreter, address, count);
+        }
+        _ => return Err(format!("unknown command `{command}`, try `help`")),
+    }
+    Ok(Action::Paused)
+}
+
+fn report_run(stop: Option<Stop>, interpreter: &Interpreter) {
+    match stop {
+        Some(Stop::Done) => {}
+        Some(stop) => println!("{stop}"),
+        None => println!("gave up after {BUDGET} instructions"),
+    }
+    print_location(interpreter);
+}
+
+fn argument<'a>(args: &[&'a str], index: usize) -> Result<&'a str, String> {
+    args.get(index)
+        .copied()
+        .ok_or_else(|| "missing argument, try `help`".to_string())
+}
+
+fn number(text: &str) -> Result<u16, String> {
+    parse_number(text).ok_or_else(|| format!("`{text}` is not a number"))
+}
+
+fn register(text: &str) -> Result<Register, String> {
+    let lower = text.to_ascii_lowercase();
+    if lower == "i" {
+        return Ok(Register::I);
+    }
+    lower
+        .strip_prefix('v')
+        .filter(|digit| digit.len() == 1)
+        .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+        .map(Register::V)
+        .ok_or_else(|| format!("`{text}` is not a register"))
+}
+
+fn condition(words: &[&str]) -> Result<Condition, String> {
+    words.join(" ").parse().map_err(|err| format!("{err}"))
+}
+
+fn print_location(interpreter: &Interpreter) {
+    print_disassembly(interpreter, interpreter.program_counter(), 1);
+}
+
+fn print_disassembly(interpreter: &Interpreter, address: u16, count: usize) {
+    let memory = interpreter.memory();
+    let start = (address as usize).min(memory.len());
+    let end = (start + count * 4).min(memory.len());
+    for (address, word, instruction) in disassemble(&memory[start..end], address)
+        .into_iter()
+        .take(count)
+    {
+        let text =
+            instruction.map_or_else(|| "???".to_string(), |instruction| instruction.to_string());
+        println!("{address:04X}  {word:04X}  {text}");
+    }
+}
+
+fn print_registers(interpreter: &Interpreter) {
+    println!(
+        "PC {:04X}  I {:04X}  SP {:02X}  DT {:02X}  ST {:02X}",
+        interpreter.program_counter(),
+        interpreter.index_register(),
+        interpreter.stack_pointer(),
+        interpreter.delay_timer(),
+        interpreter.sound_timer()
+    );
+    let registers: Vec<String> = interpreter
+        .registers()
+        .iter()
+        .enumerate()
+        .map(|(x, value)| format!("V{x:X} {value:02X}"))
+        .collect();
+    println!("{}", registers.join("  "));
+    let stack: Vec<String> = interpreter
+        .stack()
+        .iter()
+        .map(|address| format!("{address:04X}"))
+        .collect();
+    println!("Stack [{}]", stack.join(" "));
+}
+
+fn print_info(debugger: &Debugger) {
+    for address in debugger.breakpoints() {
+        println!("Breakpoint at {address:04X}");
+    }
+    for (index, (address, condition)) in debugger.conditions().iter().enumerate() {
+        match address {
+            Some(address) => println!("Condition {index} at {address:04X}: {condition}"),
+            None => println!("Condition {index}: {condition}"),
+        }
+    }
+    for (address, access) in debugger.watchpoints() {
+        let kind = match access {
+            Access::Read => "Read",
+            Access::Write => "Write",
+        };
+        println!("{kind} watch at {address:04X}");
+    }
+    for register in debugger.watched_registers() {
+        println!("Watching {register}");
+    }
+}
@@ -0,0 +1,109 @@
+use core::{
+    rgba_len, Filter, Interpreter, Overlay, Palette, Phosphor, Upscaler, HIRES_SCREEN_HEIGHT,
+    HIRES_SCREEN_WIDTH, RGBA_BYTES,
+};
+use sdl2::{
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    render::{Canvas, Texture, TextureCreator},
+    video::{Window, WindowContext},
+};
+
+/// Turns the framebuffer into the picture in the window: phosphor, palette, then the
+/// upscaling filter, uploaded to one streaming texture.
+pub struct Display<'a> {
+    phosphor: Phosphor,
+    palette: Palette,
+    upscaler: Upscaler,
+    texture: Texture<'a>,
+    width: u32,
+    /// The display at one pixel per CHIP-8 pixel.
+    native: Vec<u8>,
+    /// The display at window size, as uploaded to `texture`.
+    pixels: Vec<u8>,
+}
+
+impl<'a> Display<'a> {
+    pub fn new(
+        texture_creator: &'a TextureCreator<WindowContext>,
+        width: u32,
+        height: u32,
+        phosphor: Phosphor,
+        palette: Palette,
+        upscaler: Upscaler,
+    ) -> Result<Display<'a>, String> {
+        let texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+            .map_err(|err| err.to_string())?;
+        Ok(Display {
+            phosphor,
+            palette,
+            upscaler,
+            texture,
+            width,
+            native: vec![0; rgba_len(HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT, 1)],
+            pixels: vec![0; rgba_len(width as usize, height as usize, 1)],
+        })
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn toggle_phosphor(&mut self) -> bool {
+        self.phosphor.set_enabled(!self.phosphor.is_enabled());
+        self.phosphor.is_enabled()
+    }
+
+    pub fn next_palette(&mut self) -> Palette {
+        self.palette = self.palette.next();
+        self.phosphor.invalidate();
+        self.palette
+    }
+
+    pub fn next_filter(&mut self) -> Filter {
+        self.upscaler.filter = self.upscaler.filter.next();
+        self.phosphor.invalidate();
+        self.upscaler.filter
+    }
+
+    pub fn next_overlay(&mut self) -> Overlay {
+        self.upscaler.overlay = self.upscaler.overlay.next();
+        self.phosphor.invalidate();
+        self.upscaler.overlay
+    }
+
+    /// Redraws the rows that changed or are still fading, keeping the others from
+    /// earlier frames, then shows the picture.
+    pub fn draw(&mut self, interpreter: &mut Interpreter, canvas: &mut Canvas<Window>) {
+        let rows = self.phosphor.update(interpreter);
+        if rows != 0 {
+            let width = interpreter.screen_width();
+            let height = interpreter.screen_height();
+            let scale = self.width as usize / width;
+            let native = &mut self.native[..rgba_len(width, height, 1)];
+            self.phosphor.render_rgba(&self.palette, 1, rows, native);
+            let rows = self
+                .upscaler
+                .apply(native, width, height, scale, rows, &mut self.pixels);
+
+            // upload the band from the first to the last row written
+            let top = rows.trailing_zeros() as usize;
+            let bottom = 64 - rows.leading_zeros() as usize;
+            if top < bottom {
+                let stride = self.width as usize * RGBA_BYTES;
+                let band = Rect::new(
+                    0,
+                    (top * scale) as i32,
+                    self.width,
+                    ((bottom - top) * scale) as u32,
+                );
+                self.texture
+                    .update(band, &self.pixels[top * scale * stride..], stride)
+                    .unwrap();
+            }
+        }
+        canvas.copy(&self.texture, None, None).unwrap();
+        canvas.present();
+    }
+}
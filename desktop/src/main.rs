@@ -1,20 +1,145 @@
-use core::{Interpreter, SCREEN_HEIGHT, SCREEN_WIDTH};
-use sdl2::{
-    event::Event, keyboard::Keycode, pixels::Color, rect::Rect, render::Canvas, video::Window,
+mod audio;
+mod console;
+mod display;
+
+use audio::{Speaker, DEFAULT_VOLUME};
+use console::Action;
+use core::{
+    debugger::Debugger, gdb::GdbServer, Chip8Error, Filter, FrameStatus, GifRecorder, Interpreter,
+    Movie, MoviePlayer, Overlay, Palette, Phosphor, Quirks, Rewind, Timing, TraceWriter, Upscaler,
+    DEFAULT_DECAY_FRAMES, DEFAULT_FREQUENCY, DEFAULT_TICKS_PER_FRAME, HIRES_SCREEN_WIDTH,
+    SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+use display::Display;
+use sdl2::{event::Event, keyboard::Keycode, render::Canvas, video::Window};
+use std::{
+    env,
+    fs::{self, File},
+    io::Read,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
 };
-use std::{env, fs::File, io::Read};
 
 const SCALE: u32 = 24;
 const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
 const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
-const TICKS_PER_FRAME: usize = 5;
+const REWIND_INTERVAL: usize = 1;
+const REWIND_CAPACITY: usize = 600;
+const REWIND_KEY: Keycode = Keycode::Backspace;
+const PAUSE_KEY: Keycode = Keycode::P;
+const MUTE_KEY: Keycode = Keycode::M;
+const PHOSPHOR_KEY: Keycode = Keycode::F9;
+const PALETTE_KEY: Keycode = Keycode::F10;
+const FILTER_KEY: Keycode = Keycode::F11;
+const OVERLAY_KEY: Keycode = Keycode::O;
+const SCREENSHOT_KEY: Keycode = Keycode::F12;
+const RECORD_KEY: Keycode = Keycode::G;
+/// Image pixels per hires pixel in screenshots and recordings.
+const CAPTURE_SCALE: usize = 4;
+
+struct Options {
+    rom_path: String,
+    quirks: Quirks,
+    xo_chip: bool,
+    seed: Option<u64>,
+    ticks_per_frame: usize,
+    timing: Timing,
+    gdb_port: Option<u16>,
+    trace_path: Option<String>,
+    record_path: Option<String>,
+    play_path: Option<String>,
+    frequency: f32,
+    volume: f32,
+    muted: bool,
+    phosphor: bool,
+    decay_frames: u8,
+    palette: Palette,
+    filter: Filter,
+    overlay: Overlay,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Option<Options> {
+        let mut rom_path = None;
+        let mut quirks = Quirks::default();
+        let mut xo_chip = false;
+        let mut seed = None;
+        let mut ticks_per_frame = DEFAULT_TICKS_PER_FRAME;
+        let mut timing = Timing::Uniform;
+        let mut gdb_port = None;
+        let mut trace_path = None;
+        let mut record_path = None;
+        let mut play_path = None;
+        let mut frequency = DEFAULT_FREQUENCY;
+        let mut volume = DEFAULT_VOLUME;
+        let mut muted = false;
+        let mut phosphor = false;
+        let mut decay_frames = DEFAULT_DECAY_FRAMES;
+        let mut palette = Palette::default();
+        let mut filter = Filter::default();
+        let mut overlay = Overlay::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--quirks" => quirks = Quirks::from_name(args.next()?)?,
+                "--xo-chip" => xo_chip = true,
+                "--seed" => seed = Some(args.next()?.parse().ok()?),
+                "--ticks" => ticks_per_frame = args.next()?.parse().ok()?,
+                "--timing" => timing = Timing::from_name(args.next()?)?,
+                "--gdb" => gdb_port = Some(args.next()?.parse().ok()?),
+                "--trace" => trace_path = Some(args.next()?.clone()),
+                "--record" => record_path = Some(args.next()?.clone()),
+                "--play" => play_path = Some(args.next()?.clone()),
+                "--frequency" => frequency = args.next()?.parse().ok()?,
+                "--volume" => volume = args.next()?.parse().ok()?,
+                "--mute" => muted = true,
+                "--phosphor" => phosphor = true,
+                "--decay" => decay_frames = args.next()?.parse().ok()?,
+                "--palette" => palette = Palette::from_name(args.next()?)?,
+                "--filter" => filter = Filter::from_name(args.next()?)?,
+                "--overlay" => overlay = Overlay::from_name(args.next()?)?,
+                _ if rom_path.is_none() => rom_path = Some(arg.clone()),
+                _ => return None,
+            }
+        }
+
+        Some(Options {
+            rom_path: rom_path?,
+            quirks,
+            xo_chip,
+            seed,
+            ticks_per_frame,
+            timing,
+            gdb_port,
+            trace_path,
+            record_path,
+            play_path,
+            frequency,
+            volume,
+            muted,
+            phosphor,
+            decay_frames,
+            palette,
+            filter,
+            overlay,
+        })
+    }
+}
 
 fn main() {
     let args: Vec<_> = env::args().collect();
-    if args.len() != 2 {
-        println!("Hello");
+    let Some(options) = Options::parse(&args[1..]) else {
+        println!(
+            "Usage: desktop [--quirks vip|chip48|schip|xochip] [--xo-chip] [--seed <n>] \
+             [--ticks <n>] [--timing uniform|vip] [--gdb <port>] [--trace <file>] \
+             [--record <file>] [--play <file>] [--frequency <hz>] [--volume <0-1>] \
+             [--mute] [--phosphor] [--decay <frames>] \
+             [--palette classic|lcd|amber|octo|high-contrast] \
+             [--filter nearest|scale2x|epx|scale3x] [--overlay none|scanlines|grid] <rom>"
+        );
         return;
-    }
+    };
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
@@ -28,15 +153,96 @@ fn main() {
     let mut canvas = window.into_canvas().present_vsync().build().unwrap();
     canvas.clear();
     canvas.present();
+    let texture_creator = canvas.texture_creator();
+    let mut display = Display::new(
+        &texture_creator,
+        WINDOW_WIDTH,
+        WINDOW_HEIGHT,
+        Phosphor::new(options.phosphor, options.decay_frames),
+        options.palette,
+        Upscaler {
+            filter: options.filter,
+            overlay: options.overlay,
+        },
+    )
+    .unwrap();
+
+    let mut recorder: Option<GifRecorder> = None;
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
-    let mut chip8 = Interpreter::new();
+    let mut speaker = match sdl_context
+        .audio()
+        .and_then(|audio| Speaker::open(&audio, options.volume, options.muted))
+    {
+        Ok(device) => Some(device),
+        Err(err) => {
+            println!("Sound disabled: {err}");
+            None
+        }
+    };
 
-    let mut rom = File::open(&args[1]).expect("Unable to open file");
+    let mut chip8 = Interpreter::with_quirks(options.quirks);
+    chip8.set_xo_chip(options.xo_chip);
+    chip8.set_seed(options.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default()
+    }));
+    chip8.set_buzzer_frequency(options.frequency);
+    chip8.set_ticks_per_frame(options.ticks_per_frame);
+    chip8.set_timing(options.timing);
+
+    if let Some(path) = &options.trace_path {
+        match TraceWriter::create(path) {
+            Ok(writer) => chip8.set_trace_sink(Some(Box::new(writer))),
+            Err(err) => {
+                println!("Unable to create trace file: {err}");
+                return;
+            }
+        }
+    }
+
+    let mut rom = File::open(&options.rom_path).expect("Unable to open file");
     let mut buffer = Vec::new();
     rom.read_to_end(&mut buffer).unwrap();
-    chip8.load(&buffer);
+    if let Err(err) = chip8.load(&buffer) {
+        println!("Unable to load ROM: {err}");
+        return;
+    }
+
+    let mut player = match &options.play_path {
+        Some(path) => match open_movie(path, &mut chip8) {
+            Ok(player) => {
+                let frames = player.movie().frames.len();
+                println!("Playing {frames} frames from {path}");
+                Some(player)
+            }
+            Err(err) => {
+                println!("Unable to play {path}: {err}");
+                return;
+            }
+        },
+        None => None,
+    };
+    let mut movie = options.record_path.as_ref().map(|_| Movie::new(&chip8));
+
+    let mut fault = None;
+    let mut rewind = Rewind::new(REWIND_INTERVAL, REWIND_CAPACITY);
+    let mut rewinding = false;
+    let mut debugger = Debugger::new();
+    let mut paused = false;
+    let mut gdb = match options.gdb_port.map(GdbServer::bind).transpose() {
+        Ok(gdb) => gdb,
+        Err(err) => {
+            println!("Unable to start gdb server: {err}");
+            return;
+        }
+    };
+    if let Some(port) = options.gdb_port {
+        println!("Listening for gdb on 127.0.0.1:{port}");
+    }
 
     'gameloop: loop {
         for event in event_pump.poll_iter() {
@@ -48,11 +254,82 @@ fn main() {
                 } => {
                     break 'gameloop;
                 }
+                Event::KeyDown {
+                    keycode: Some(REWIND_KEY),
+                    ..
+                } if player.is_none() && movie.is_none() => rewinding = true,
+                Event::KeyUp {
+                    keycode: Some(REWIND_KEY),
+                    ..
+                } => rewinding = false,
+                Event::KeyDown {
+                    keycode: Some(PAUSE_KEY),
+                    ..
+                } if !paused => {
+                    paused = true;
+                    println!("Paused, type `help` for debugger commands");
+                }
+                Event::KeyDown {
+                    keycode: Some(MUTE_KEY),
+                    ..
+                } => {
+                    if let Some(speaker) = speaker.as_mut() {
+                        speaker.muted = !speaker.muted;
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(PHOSPHOR_KEY),
+                    ..
+                } => {
+                    let enabled = display.toggle_phosphor();
+                    println!("Phosphor {}", if enabled { "on" } else { "off" });
+                }
+                Event::KeyDown {
+                    keycode: Some(PALETTE_KEY),
+                    ..
+                } => println!("Palette: {}", display.next_palette().name),
+                Event::KeyDown {
+                    keycode: Some(FILTER_KEY),
+                    ..
+                } => println!("Filter: {:?}", display.next_filter()),
+                Event::KeyDown {
+                    keycode: Some(OVERLAY_KEY),
+                    ..
+                } => println!("Overlay: {:?}", display.next_overlay()),
+                Event::KeyDown {
+                    keycode: Some(SCREENSHOT_KEY),
+                    ..
+                } => {
+                    let zoom = HIRES_SCREEN_WIDTH / chip8.screen_width();
+                    let png = chip8.screenshot_png(display.palette(), zoom * CAPTURE_SCALE);
+                    write_capture(&options.rom_path, "png", &png);
+                }
+                Event::KeyDown {
+                    keycode: Some(RECORD_KEY),
+                    ..
+                } => match recorder.take() {
+                    Some(recorder) => {
+                        write_capture(&options.rom_path, "gif", &recorder.finish());
+                    }
+                    None => {
+                        recorder = Some(GifRecorder::new(display.palette(), CAPTURE_SCALE));
+                        println!("Recording, press G to stop");
+                    }
+                },
                 Event::KeyDown {
                     keycode: Some(key), ..
                 } => {
                     if let Some(k) = match_key_to_btn(key) {
                         chip8.keypress(k, true)
+                    } else if let Some(slot) = match_key_to_save_slot(key) {
+                        save_state(&chip8, &options.rom_path, slot);
+                    } else if let Some(slot) = match_key_to_load_slot(key) {
+                        if player.is_some() || movie.is_some() {
+                            println!("States can't be loaded while a movie records or plays");
+                        } else if load_state(&mut chip8, &options.rom_path, slot) {
+                            rewind.clear();
+                            clear_fault(&mut fault, &mut canvas);
+                        }
                     }
                 }
                 Event::KeyUp {
@@ -66,34 +343,158 @@ fn main() {
             }
         }
 
-        for _ in 0..TICKS_PER_FRAME {
-            chip8.tick();
+        if let Some(server) = gdb.as_mut() {
+            if let Err(err) = server.poll(&mut debugger, &mut chip8) {
+                println!("gdb: {err}");
+            }
+        }
+
+        if rewinding {
+            if rewind.step_back(&mut chip8) {
+                clear_fault(&mut fault, &mut canvas);
+            }
+        } else if gdb.as_ref().is_some_and(|server| server.is_connected()) {
+            // the server runs the frames while a client is attached
+        } else if fault.is_none() && !paused {
+            if let Some(ended) = player.take_if(|player| !player.feed(&mut chip8)) {
+                println!("Movie ended after {} frames", ended.frame());
+            }
+            match debugger.run_frame(&mut chip8) {
+                Ok(summary) => {
+                    if let FrameStatus::Fault(err) = summary.status {
+                        println!("Fault: {err}");
+                        canvas
+                            .window_mut()
+                            .set_title(&format!("Chip-8 - fault: {err}"))
+                            .unwrap();
+                        fault = Some(err);
+                    }
+                    rewind.record(&chip8);
+                    if let Some(desync) = player.as_mut().and_then(|player| player.verify(&chip8)) {
+                        println!("Movie {desync}");
+                    }
+                    if let Some(movie) = movie.as_mut() {
+                        movie.record(&chip8);
+                    }
+                }
+                Err(stop) => {
+                    console::report_pause(stop, &chip8);
+                    paused = true;
+                }
+            }
+        }
+        display.draw(&mut chip8, &mut canvas);
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(&chip8);
+        }
+
+        let halted_by_gdb = gdb
+            .as_ref()
+            .is_some_and(|server| server.is_connected() && !server.is_running());
+        let frozen = paused || rewinding || fault.is_some() || halted_by_gdb;
+        if let Some(Err(err)) = speaker
+            .as_mut()
+            .filter(|_| !frozen)
+            .map(|speaker| speaker.play_frame(&mut chip8))
+        {
+            println!("Sound disabled: {err}");
+            speaker = None;
+        }
+
+        if paused {
+            match console::prompt(&mut debugger, &mut chip8) {
+                Action::Resume => paused = false,
+                Action::Paused => {}
+                Action::Quit => break 'gameloop,
+            }
+        }
+    }
+
+    if let Some(recorder) = recorder {
+        write_capture(&options.rom_path, "gif", &recorder.finish());
+    }
+    if let (Some(movie), Some(path)) = (movie, &options.record_path) {
+        match fs::write(path, movie.to_bytes()) {
+            Ok(()) => println!("Saved {} frames to {path}", movie.frames.len()),
+            Err(err) => println!("Unable to write {path}: {err}"),
         }
-        chip8.tick_timers();
-        draw_screen(&chip8, &mut canvas)
     }
 }
 
-fn draw_screen(interpreter: &Interpreter, canvas: &mut Canvas<Window>) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
+/// Reads a movie and applies its settings to `interpreter`, which has just loaded
+/// the ROM.
+fn open_movie(path: &str, interpreter: &mut Interpreter) -> Result<MoviePlayer, String> {
+    let data = fs::read(path).map_err(|err| err.to_string())?;
+    let movie = Movie::from_bytes(&data).map_err(|err| err.to_string())?;
+    MoviePlayer::new(movie, interpreter).map_err(|err| err.to_string())
+}
 
-    let screen_buffer = interpreter.get_screen();
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
-    for row in 0..screen_buffer.len() {
-        for col in 0..screen_buffer[0].len() {
-            if screen_buffer[row][col] {
-                let rect = Rect::new(
-                    (col as u32 * SCALE) as i32,
-                    (row as u32 * SCALE) as i32,
-                    SCALE,
-                    SCALE,
-                );
-                canvas.fill_rect(rect).unwrap();
-            }
+fn clear_fault(fault: &mut Option<Chip8Error>, canvas: &mut Canvas<Window>) {
+    if fault.take().is_some() {
+        canvas.window_mut().set_title("Chip-8").unwrap();
+    }
+}
+
+/// Writes a screenshot or recording next to the ROM as `<rom>-<n>.<extension>`, with
+/// the first unused `n`.
+fn write_capture(rom_path: &str, extension: &str, data: &[u8]) {
+    let path = (1..)
+        .map(|n| format!("{rom_path}-{n}.{extension}"))
+        .find(|path| !Path::new(path).exists())
+        .unwrap();
+    match fs::write(&path, data) {
+        Ok(()) => println!("Saved {path}"),
+        Err(err) => println!("Unable to write {path}: {err}"),
+    }
+}
+
+fn state_path(rom_path: &str, slot: usize) -> String {
+    format!("{rom_path}.state{slot}")
+}
+
+fn save_state(interpreter: &Interpreter, rom_path: &str, slot: usize) {
+    let path = state_path(rom_path, slot);
+    match fs::write(&path, interpreter.save_state()) {
+        Ok(()) => println!("Saved state to slot {slot}"),
+        Err(err) => println!("Unable to write {path}: {err}"),
+    }
+}
+
+fn load_state(interpreter: &mut Interpreter, rom_path: &str, slot: usize) -> bool {
+    let path = state_path(rom_path, slot);
+    let result = fs::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|data| interpreter.load_state(&data).map_err(|err| err.to_string()));
+    match result {
+        Ok(()) => {
+            println!("Loaded state from slot {slot}");
+            true
+        }
+        Err(err) => {
+            println!("Unable to load {path}: {err}");
+            false
         }
     }
-    canvas.present();
+}
+
+fn match_key_to_save_slot(key: Keycode) -> Option<usize> {
+    match key {
+        Keycode::F1 => Some(1),
+        Keycode::F2 => Some(2),
+        Keycode::F3 => Some(3),
+        Keycode::F4 => Some(4),
+        _ => None,
+    }
+}
+
+fn match_key_to_load_slot(key: Keycode) -> Option<usize> {
+    match key {
+        Keycode::F5 => Some(1),
+        Keycode::F6 => Some(2),
+        Keycode::F7 => Some(3),
+        Keycode::F8 => Some(4),
+        _ => None,
+    }
 }
 
 fn match_key_to_btn(key: Keycode) -> Option<usize> {
@@ -0,0 +1,102 @@
+use core::{encode_png, Interpreter};
+
+/// Gray levels for the four bitplane combinations, as in the desktop window.
+const LEVELS: [u8; 4] = [0, 255, 170, 85];
+const ASCII: [char; 4] = ['.', '#', 'o', '@'];
+
+fn rows(interpreter: &Interpreter) -> impl Iterator<Item = Vec<u8>> + '_ {
+    let width = interpreter.screen_width();
+    let framebuffer = interpreter.framebuffer();
+    (0..interpreter.screen_height()).map(move |row| framebuffer.row_pixels(row, width).collect())
+}
+
+pub fn ascii(interpreter: &Interpreter) -> String {
+    rows(interpreter)
+        .map(|row| {
+            let mut line: String = row.iter().map(|&pixel| ASCII[pixel as usize & 3]).collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+/// Plain PBM, with a pixel set when any plane is.
+pub fn pbm(interpreter: &Interpreter) -> Vec<u8> {
+    let mut out = format!(
+        "P1\n{} {}\n",
+        interpreter.screen_width(),
+        interpreter.screen_height()
+    );
+    for row in rows(interpreter) {
+        let bits: Vec<&str> = row
+            .iter()
+            .map(|&pixel| if pixel != 0 { "1" } else { "0" })
+            .collect();
+        out.push_str(&bits.join(" "));
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+/// 8-bit grayscale PNG.
+pub fn png(interpreter: &Interpreter) -> Vec<u8> {
+    let gray: Vec<u8> = rows(interpreter)
+        .flatten()
+        .map(|pixel| LEVELS[pixel as usize & 3])
+        .collect();
+    encode_png(
+        &gray,
+        interpreter.screen_width(),
+        interpreter.screen_height(),
+        1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interpreter_with_digit() -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        // draw the font sprite for 0 at (0, 0)
+        interpreter.load(&[0xD0, 0x05]).unwrap();
+        interpreter.tick().unwrap();
+        interpreter
+    }
+
+    #[test]
+    fn test_ascii() {
+        let text = ascii(&interpreter_with_digit());
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert_eq!(&lines[0][..8], "####....");
+        assert_eq!(&lines[1][..8], "#..#....");
+        assert_eq!(lines[0].len(), 64);
+    }
+
+    #[test]
+    fn test_pbm() {
+        let pbm = String::from_utf8(pbm(&interpreter_with_digit())).unwrap();
+        let mut lines = pbm.lines();
+        assert_eq!(lines.next(), Some("P1"));
+        assert_eq!(lines.next(), Some("64 32"));
+        assert!(lines.next().unwrap().starts_with("1 1 1 1 0"));
+    }
+
+    #[test]
+    fn test_png_layout() {
+        let png = png(&interpreter_with_digit());
+        assert_eq!(&png[1..4], b"PNG");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 64);
+        assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), 32);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+        // one stored block holding every filtered row
+        let idat = &png[33..];
+        assert_eq!(&idat[4..8], b"IDAT");
+        let raw_len = 32 * (64 + 1);
+        assert_eq!(u16::from_le_bytes([idat[11], idat[12]]) as usize, raw_len);
+        assert_eq!(idat[15..19], [0, 255, 255, 255]);
+    }
+}
@@ -0,0 +1,193 @@
+mod image;
+mod script;
+mod wav;
+
+use core::{Chip8Error, FrameStatus, Interpreter, Quirks, Timing, DEFAULT_TICKS_PER_FRAME};
+use script::KeyEvent;
+use std::{fs, process};
+
+const FRAME_RATE: u32 = 60;
+
+struct Options {
+    rom_path: String,
+    quirks: Quirks,
+    xo_chip: bool,
+    seed: Option<u64>,
+    frames: usize,
+    ticks_per_frame: usize,
+    timing: Timing,
+    keys_path: Option<String>,
+    png_path: Option<String>,
+    pbm_path: Option<String>,
+    wav_path: Option<String>,
+    ascii: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Option<Options> {
+        let mut rom_path = None;
+        let mut quirks = Quirks::default();
+        let mut xo_chip = false;
+        let mut seed = None;
+        let mut frames = 60;
+        let mut ticks_per_frame = DEFAULT_TICKS_PER_FRAME;
+        let mut timing = Timing::Uniform;
+        let mut keys_path = None;
+        let mut png_path = None;
+        let mut pbm_path = None;
+        let mut wav_path = None;
+        let mut ascii = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--quirks" => quirks = Quirks::from_name(args.next()?)?,
+                "--xo-chip" => xo_chip = true,
+                "--seed" => seed = Some(args.next()?.parse().ok()?),
+                "--frames" => frames = args.next()?.parse().ok()?,
+                "--ticks" => ticks_per_frame = args.next()?.parse().ok()?,
+                "--timing" => timing = Timing::from_name(args.next()?)?,
+                "--keys" => keys_path = Some(args.next()?.clone()),
+                "--png" => png_path = Some(args.next()?.clone()),
+                "--pbm" => pbm_path = Some(args.next()?.clone()),
+                "--wav" => wav_path = Some(args.next()?.clone()),
+                "--ascii" => ascii = true,
+                _ if rom_path.is_none() => rom_path = Some(arg.clone()),
+                _ => return None,
+            }
+        }
+
+        Some(Options {
+            rom_path: rom_path?,
+            quirks,
+            xo_chip,
+            seed,
+            frames,
+            ticks_per_frame,
+            timing,
+            keys_path,
+            png_path,
+            pbm_path,
+            wav_path,
+            ascii,
+        })
+    }
+}
+
+fn main() {
+    let args: Vec<_> = std::env::args().collect();
+    let Some(options) = Options::parse(&args[1..]) else {
+        println!(
+            "Usage: headless [--quirks vip|chip48|schip|xochip] [--xo-chip] [--seed <n>] \
+             [--frames <n>] [--ticks <n>] [--timing uniform|vip] [--keys <script>] \
+             [--png <file>] [--pbm <file>] [--wav <file>] [--ascii] <rom>"
+        );
+        process::exit(2);
+    };
+
+    let rom = read(&options.rom_path);
+    let keys = match &options.keys_path {
+        Some(path) => {
+            let source = String::from_utf8_lossy(&read(path)).into_owned();
+            script::parse(&source).unwrap_or_else(|err| fail(&format!("{path}: {err}")))
+        }
+        None => Vec::new(),
+    };
+
+    let mut chip8 = Interpreter::with_quirks(options.quirks);
+    chip8.set_xo_chip(options.xo_chip);
+    chip8.set_ticks_per_frame(options.ticks_per_frame);
+    chip8.set_timing(options.timing);
+    if let Some(seed) = options.seed {
+        chip8.set_seed(seed);
+    }
+    if let Err(err) = chip8.load(&rom) {
+        fail(&format!("Unable to load ROM: {err}"));
+    }
+
+    let mut audio = Vec::new();
+    let (frames, fault) = run(&mut chip8, &options, &keys, &mut audio);
+
+    println!("frames {frames}");
+    println!("hash {:016x}", chip8.screen_hash());
+    print_registers(&chip8);
+    if options.ascii {
+        print!("{}", image::ascii(&chip8));
+    }
+    if let Some(path) = &options.png_path {
+        write(path, &image::png(&chip8));
+    }
+    if let Some(path) = &options.pbm_path {
+        write(path, &image::pbm(&chip8));
+    }
+    if let Some(path) = &options.wav_path {
+        write(path, &wav::wav(&audio));
+    }
+    if let Some(err) = fault {
+        println!("fault {err}");
+        process::exit(1);
+    }
+}
+
+/// Runs until the frame budget is spent, the ROM exits or it faults, returning the
+/// number of frames completed. Each frame's sound is appended to `audio` when a WAV
+/// file was requested.
+fn run(
+    chip8: &mut Interpreter,
+    options: &Options,
+    keys: &[KeyEvent],
+    audio: &mut Vec<f32>,
+) -> (usize, Option<Chip8Error>) {
+    let mut keys = keys.iter().peekable();
+    let mut samples = vec![0.0; (wav::SAMPLE_RATE / FRAME_RATE) as usize];
+    for frame in 0..options.frames {
+        while let Some(event) = keys.next_if(|event| event.frame <= frame) {
+            chip8.keypress(event.key, event.pressed);
+        }
+        let status = chip8.run_frame().status;
+        if let FrameStatus::Fault(err) = status {
+            return (frame, Some(err));
+        }
+        if options.wav_path.is_some() {
+            chip8.fill_audio(&mut samples, wav::SAMPLE_RATE);
+            audio.extend_from_slice(&samples);
+        }
+        if status == FrameStatus::Halted {
+            return (frame + 1, None);
+        }
+    }
+    (options.frames, None)
+}
+
+fn print_registers(chip8: &Interpreter) {
+    println!(
+        "PC {:04X} I {:04X} SP {:02X} DT {:02X} ST {:02X}",
+        chip8.program_counter(),
+        chip8.index_register(),
+        chip8.stack_pointer(),
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    );
+    let registers: Vec<String> = chip8
+        .registers()
+        .iter()
+        .enumerate()
+        .map(|(x, value)| format!("V{x:X} {value:02X}"))
+        .collect();
+    println!("{}", registers.join(" "));
+}
+
+fn read(path: &str) -> Vec<u8> {
+    fs::read(path).unwrap_or_else(|err| fail(&format!("Unable to read {path}: {err}")))
+}
+
+fn write(path: &str, bytes: &[u8]) {
+    if let Err(err) = fs::write(path, bytes) {
+        fail(&format!("Unable to write {path}: {err}"));
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{message}");
+    process::exit(1);
+}
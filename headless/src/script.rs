@@ -0,0 +1,90 @@
+/// A key change at the start of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: usize,
+    pub key: usize,
+    pub pressed: bool,
+}
+
+/// Parses a key script: one `<frame> press|release <key>` per line, with the key in
+/// hex and `#` starting a comment, e.g.
+///
+/// ```text
+/// 30 press 5
+/// 32 release 5
+/// ```
+pub fn parse(source: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut events = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {message}", index + 1);
+        let [frame, action, key] = words[..] else {
+            return Err(error("expected `<frame> press|release <key>`"));
+        };
+        let frame = frame
+            .parse()
+            .map_err(|_| error(&format!("`{frame}` is not a frame number")))?;
+        let pressed = match action {
+            "press" => true,
+            "release" => false,
+            _ => return Err(error(&format!("unknown action `{action}`"))),
+        };
+        let key = usize::from_str_radix(key, 16)
+            .ok()
+            .filter(|&key| key < 16)
+            .ok_or_else(|| error(&format!("`{key}` is not a key from 0 to F")))?;
+        events.push(KeyEvent {
+            frame,
+            key,
+            pressed,
+        });
+    }
+    events.sort_by_key(|event| event.frame);
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let events = parse("# start\n30 release a\n\n10 press A  # jump\n").unwrap();
+        assert_eq!(
+            events,
+            [
+                KeyEvent {
+                    frame: 10,
+                    key: 0xA,
+                    pressed: true
+                },
+                KeyEvent {
+                    frame: 30,
+                    key: 0xA,
+                    pressed: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse("1 press 5\nten press 5").unwrap_err(),
+            "line 2: `ten` is not a frame number"
+        );
+        assert_eq!(
+            parse("1 hold 5").unwrap_err(),
+            "line 1: unknown action `hold`"
+        );
+        assert_eq!(
+            parse("1 press 10").unwrap_err(),
+            "line 1: `10` is not a key from 0 to F"
+        );
+        assert!(parse("1 press").is_err());
+    }
+}
@@ -0,0 +1,49 @@
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// 16-bit mono PCM WAV.
+pub fn wav(samples: &[f32]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend(b"RIFF");
+    out.extend((36 + data_len).to_le_bytes());
+    out.extend(b"WAVE");
+
+    out.extend(b"fmt ");
+    out.extend(16u32.to_le_bytes());
+    // PCM, one channel
+    out.extend(1u16.to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend(SAMPLE_RATE.to_le_bytes());
+    out.extend((SAMPLE_RATE * 2).to_le_bytes());
+    // block align, bits per sample
+    out.extend(2u16.to_le_bytes());
+    out.extend(16u16.to_le_bytes());
+
+    out.extend(b"data");
+    out.extend(data_len.to_le_bytes());
+    for &sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        out.extend(value.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_layout() {
+        let wav = wav(&[0.0, 1.0, -1.0]);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 42);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(
+            u32::from_le_bytes(wav[24..28].try_into().unwrap()),
+            SAMPLE_RATE
+        );
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 6);
+        assert_eq!(wav[44..], [0x00, 0x00, 0xFF, 0x7F, 0x01, 0x80]);
+    }
+}
@@ -0,0 +1,62 @@
+use core::Interpreter;
+use wasm_bindgen::prelude::*;
+use web_sys::AudioContext;
+
+const DEFAULT_VOLUME: f32 = 0.25;
+const FRAME_RATE: f32 = 60.0;
+/// How far ahead of the audio clock buffers may queue up before frames are dropped.
+const MAX_LATENCY: f64 = 0.1;
+
+/// Plays the interpreter's audio through WebAudio: one buffer per frame, queued
+/// back to back.
+pub struct Audio {
+    context: AudioContext,
+    samples: Vec<f32>,
+    next_start: f64,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Audio {
+    pub fn new() -> Result<Audio, JsValue> {
+        let context = AudioContext::new()?;
+        let sample_rate = context.sample_rate();
+        Ok(Audio {
+            context,
+            samples: vec![0.0; (sample_rate / FRAME_RATE) as usize],
+            next_start: 0.0,
+            volume: DEFAULT_VOLUME,
+            muted: false,
+        })
+    }
+
+    /// Browsers start audio suspended until a user gesture.
+    pub fn resume(&self) -> Result<(), JsValue> {
+        self.context.resume().map(|_| ())
+    }
+
+    pub fn play_frame(&mut self, chip8: &mut Interpreter) -> Result<(), JsValue> {
+        let sample_rate = self.context.sample_rate();
+        chip8.fill_audio(&mut self.samples, sample_rate as u32);
+        let now = self.context.current_time();
+        if !chip8.is_buzzer_active() || self.muted || self.next_start > now + MAX_LATENCY {
+            return Ok(());
+        }
+
+        for sample in self.samples.iter_mut() {
+            *sample *= self.volume;
+        }
+        let buffer = self
+            .context
+            .create_buffer(1, self.samples.len() as u32, sample_rate)?;
+        buffer.copy_to_channel(&self.samples, 0)?;
+
+        let source = self.context.create_buffer_source()?;
+        source.set_buffer(Some(&buffer));
+        source.connect_with_audio_node(&self.context.destination())?;
+        let start = self.next_start.max(now);
+        source.start_with_when(start)?;
+        self.next_start = start + self.samples.len() as f64 / sample_rate as f64;
+        Ok(())
+    }
+}
@@ -1,19 +1,46 @@
+mod audio;
+
+use audio::Audio;
 use core::*;
 use js_sys::Uint8Array;
-use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, KeyboardEvent};
+use wasm_bindgen::{prelude::*, Clamped};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, KeyboardEvent};
+
+const REWIND_INTERVAL: usize = 1;
+const REWIND_CAPACITY: usize = 600;
+
+/// `FrameSummary` as seen from JavaScript.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct Frame {
+    pub instructions: usize,
+    pub screen_changed: bool,
+    pub sound_active: bool,
+    pub halted: bool,
+}
 
 #[wasm_bindgen]
 pub struct InterpreterWasm {
     chip8: Interpreter,
+    rewind: Rewind,
     context: CanvasRenderingContext2d,
+    phosphor: Phosphor,
+    palette: Palette,
+    upscaler: Upscaler,
+    /// The display at one pixel per CHIP-8 pixel.
+    native: Vec<u8>,
+    /// The display at canvas size.
+    pixels: Vec<u8>,
+    recorder: Option<GifRecorder>,
+    audio: Option<Audio>,
 }
 
 #[wasm_bindgen]
 impl InterpreterWasm {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Result<InterpreterWasm, JsValue> {
-        let chip8 = Interpreter::new();
+        let mut chip8 = Interpreter::new();
+        chip8.set_seed((js_sys::Math::random() * u64::MAX as f64) as u64);
 
         let document = web_sys::window().unwrap().document().unwrap();
         let canvas = document.get_element_by_id("canvas").unwrap();
@@ -29,22 +56,191 @@ impl InterpreterWasm {
             .dyn_into::<CanvasRenderingContext2d>()
             .unwrap();
 
-        Ok(InterpreterWasm { chip8, context })
+        Ok(InterpreterWasm {
+            chip8,
+            rewind: Rewind::new(REWIND_INTERVAL, REWIND_CAPACITY),
+            context,
+            phosphor: Phosphor::default(),
+            palette: Palette::default(),
+            upscaler: Upscaler::default(),
+            native: Vec::new(),
+            pixels: Vec::new(),
+            recorder: None,
+            audio: Audio::new().ok(),
+        })
+    }
+
+    /// Runs one 60 Hz frame, throwing on a fault.
+    #[wasm_bindgen]
+    pub fn run_frame(&mut self) -> Result<Frame, JsValue> {
+        let summary = self.chip8.run_frame();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(&self.chip8);
+        }
+        match summary.status {
+            FrameStatus::Fault(err) => Err(to_js_error(err)),
+            status => Ok(Frame {
+                instructions: summary.instructions,
+                screen_changed: summary.screen_changed,
+                sound_active: summary.sound_active,
+                halted: status == FrameStatus::Halted,
+            }),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn set_ticks_per_frame(&mut self, ticks: usize) {
+        self.chip8.set_ticks_per_frame(ticks);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_timing(&mut self, name: &str) -> Result<(), JsValue> {
+        let timing = Timing::from_name(name)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown timing mode: {name}")))?;
+        self.chip8.set_timing(timing);
+        Ok(())
+    }
+
+    /// Call after `run_frame` to queue this frame's sound.
+    #[wasm_bindgen]
+    pub fn play_audio(&mut self) -> Result<(), JsValue> {
+        match self.audio.as_mut() {
+            Some(audio) => audio.play_frame(&mut self.chip8),
+            None => Ok(()),
+        }
+    }
+
+    /// Call from a user gesture such as loading a ROM, or the browser keeps audio off.
+    #[wasm_bindgen]
+    pub fn resume_audio(&self) -> Result<(), JsValue> {
+        match self.audio.as_ref() {
+            Some(audio) => audio.resume(),
+            None => Ok(()),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.chip8.set_buzzer_frequency(frequency);
     }
 
     #[wasm_bindgen]
-    pub fn tick(&mut self) {
-        self.chip8.tick();
+    pub fn set_volume(&mut self, volume: f32) {
+        if let Some(audio) = self.audio.as_mut() {
+            audio.volume = volume.clamp(0.0, 1.0);
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn set_muted(&mut self, muted: bool) {
+        if let Some(audio) = self.audio.as_mut() {
+            audio.muted = muted;
+        }
     }
 
+    /// Lets switched-off pixels fade out instead of vanishing, which hides flicker.
     #[wasm_bindgen]
-    pub fn tick_timers(&mut self) {
-        self.chip8.tick_timers();
+    pub fn set_phosphor(&mut self, enabled: bool) {
+        self.phosphor.set_enabled(enabled);
+    }
+
+    /// How many frames a pixel takes to fade out while the phosphor is on.
+    #[wasm_bindgen]
+    pub fn set_decay_frames(&mut self, frames: u8) {
+        self.phosphor.set_decay_frames(frames);
+    }
+
+    /// Switches to a built-in theme: `classic`, `lcd`, `amber`, `octo` or
+    /// `high-contrast`.
+    #[wasm_bindgen]
+    pub fn set_palette(&mut self, name: &str) -> Result<(), JsValue> {
+        self.palette = Palette::from_name(name)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown palette: {name}")))?;
+        self.phosphor.invalidate();
+        Ok(())
+    }
+
+    /// Picks the upscaling filter: `nearest`, `scale2x`, `epx` or `scale3x`.
+    #[wasm_bindgen]
+    pub fn set_filter(&mut self, name: &str) -> Result<(), JsValue> {
+        self.upscaler.filter = Filter::from_name(name)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown filter: {name}")))?;
+        self.phosphor.invalidate();
+        Ok(())
+    }
+
+    /// Picks the overlay: `none`, `scanlines` or `grid`.
+    #[wasm_bindgen]
+    pub fn set_overlay(&mut self, name: &str) -> Result<(), JsValue> {
+        self.upscaler.overlay = Overlay::from_name(name)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown overlay: {name}")))?;
+        self.phosphor.invalidate();
+        Ok(())
+    }
+
+    /// The display as a PNG file in the current palette, `scale` image pixels per
+    /// hires pixel.
+    #[wasm_bindgen]
+    pub fn screenshot_png(&self, scale: usize) -> Result<Vec<u8>, JsValue> {
+        check_scale(scale)?;
+        let zoom = HIRES_SCREEN_WIDTH / self.chip8.screen_width();
+        Ok(self.chip8.screenshot_png(&self.palette, zoom * scale))
+    }
+
+    /// Starts recording every frame `run_frame` runs into an animated GIF.
+    #[wasm_bindgen]
+    pub fn start_recording(&mut self, scale: usize) -> Result<(), JsValue> {
+        check_scale(scale)?;
+        self.recorder = Some(GifRecorder::new(&self.palette, scale));
+        Ok(())
+    }
+
+    /// Stops recording and returns the GIF file, if a recording was running.
+    #[wasm_bindgen]
+    pub fn stop_recording(&mut self) -> Option<Vec<u8>> {
+        self.recorder.take().map(GifRecorder::finish)
     }
 
     #[wasm_bindgen]
     pub fn reset(&mut self) {
         self.chip8.reset();
+        self.rewind.clear();
+    }
+
+    /// Call once per frame while the game is running to build up rewind history.
+    #[wasm_bindgen]
+    pub fn record_frame(&mut self) {
+        self.rewind.record(&self.chip8);
+    }
+
+    /// Steps one frame back in the rewind history. Returns `false` once it runs out.
+    #[wasm_bindgen]
+    pub fn rewind(&mut self) -> bool {
+        self.rewind.step_back(&mut self.chip8)
+    }
+
+    #[wasm_bindgen]
+    pub fn set_quirks(&mut self, profile: &str) -> Result<(), JsValue> {
+        let quirks = Quirks::from_name(profile)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown quirks profile: {profile}")))?;
+        self.chip8.set_quirks(quirks);
+        Ok(())
+    }
+
+    /// Turns XO-CHIP mode on or off. Call it before `load`, since it resizes memory.
+    #[wasm_bindgen]
+    pub fn set_xo_chip(&mut self, enabled: bool) {
+        self.chip8.set_xo_chip(enabled);
+    }
+
+    #[wasm_bindgen]
+    pub fn seed(&self) -> u64 {
+        self.chip8.seed()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_seed(&mut self, seed: u64) {
+        self.chip8.set_seed(seed);
     }
 
     #[wasm_bindgen]
@@ -56,26 +252,75 @@ impl InterpreterWasm {
     }
 
     #[wasm_bindgen]
-    pub fn load(&mut self, data: Uint8Array) {
-        self.chip8.load(&data.to_vec());
+    pub fn load(&mut self, data: Uint8Array) -> Result<(), JsValue> {
+        self.chip8.load(&data.to_vec()).map_err(to_js_error)
+    }
+
+    #[wasm_bindgen]
+    pub fn save_state(&self) -> Vec<u8> {
+        self.chip8.save_state()
     }
 
     #[wasm_bindgen]
-    pub fn draw_screen(&mut self, scale: usize) {
-        let display = self.chip8.get_screen();
-        for row in 0..display.len() {
-            for col in 0..display[0].len() {
-                if display[row][col] {
-                    self.context.fill_rect(
-                        (col * scale) as f64,
-                        (row * scale) as f64,
-                        scale as f64,
-                        scale as f64,
-                    );
-                }
-            }
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        self.chip8
+            .load_state(data)
+            .map_err(|err| JsValue::from_str(&err.to_string()))?;
+        self.rewind.clear();
+        Ok(())
+    }
+
+    /// Redraws the rows that changed or are still fading since the last call, with a
+    /// single `putImageData` of the RGBA image kept in `pixels`.
+    #[wasm_bindgen]
+    pub fn draw_screen(&mut self, scale: usize) -> Result<(), JsValue> {
+        let width = self.chip8.screen_width();
+        let height = self.chip8.screen_height();
+        // checked after converting, since a scale of 1 rounds down to 0 in hires
+        let scale = scale * SCREEN_WIDTH / width;
+        check_scale(scale)?;
+        let rows = self.phosphor.update(&mut self.chip8) & (u64::MAX >> (64 - height));
+        if rows == 0 {
+            return Ok(());
         }
+        self.native.resize(rgba_len(width, height, 1), 0);
+        self.pixels.resize(rgba_len(width, height, scale), 0);
+        self.phosphor
+            .render_rgba(&self.palette, 1, rows, &mut self.native);
+        let rows = self
+            .upscaler
+            .apply(&self.native, width, height, scale, rows, &mut self.pixels);
+
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&self.pixels),
+            (width * scale) as u32,
+            (height * scale) as u32,
+        )?;
+        // only the band from the first to the last row written is copied to the canvas
+        let top = rows.trailing_zeros() as usize;
+        let bottom = 64 - rows.leading_zeros() as usize;
+        self.context
+            .put_image_data_with_dirty_x_and_dirty_y_and_dirty_width_and_dirty_height(
+                &image,
+                0.0,
+                0.0,
+                0.0,
+                (top * scale) as f64,
+                (width * scale) as f64,
+                ((bottom - top) * scale) as f64,
+            )
+    }
+}
+
+fn to_js_error(err: Chip8Error) -> JsValue {
+    JsValue::from_str(&err.to_string())
+}
+
+fn check_scale(scale: usize) -> Result<(), JsValue> {
+    if scale == 0 {
+        return Err(JsValue::from_str("Scale must be at least 1"));
     }
+    Ok(())
 }
 
 fn match_key_to_btn(key: &str) -> Option<usize> {
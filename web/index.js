@@ -3,34 +3,103 @@ import init, * as wasm from "./wasm.js";
 const WIDTH = 64;
 const HEIGHT = 32;
 const SCALE = 24;
-const TICKS_PER_FRAME = 5;
+const REWIND_KEY = "Backspace";
+const CAPTURE_SCALE = 4;
 let anim_frame = 0;
+let rewinding = false;
+let faulted = false;
 
 const canvas = document.getElementById("canvas");
 canvas.width = WIDTH * SCALE;
 canvas.height = HEIGHT * SCALE;
 
-const context = canvas.getContext("2d");
-context.fillStyle = "black";
-context.fillRect(0, 0, WIDTH * SCALE, HEIGHT * SCALE);
-
 const input = document.getElementById("fileinput");
+const quirks = document.getElementById("quirks");
+const xoChip = document.getElementById("xo-chip");
+const mute = document.getElementById("mute");
+const phosphor = document.getElementById("phosphor");
+const decay = document.getElementById("decay");
+const palette = document.getElementById("palette");
+const filter = document.getElementById("filter");
+const overlay = document.getElementById("overlay");
+const screenshot = document.getElementById("screenshot");
+const record = document.getElementById("record");
+const status = document.getElementById("status");
 
 async function run() {
   await init();
   let chip8 = new wasm.InterpreterWasm();
+  chip8.set_palette(palette.value);
+  chip8.set_filter(filter.value);
+  chip8.set_overlay(overlay.value);
+  chip8.draw_screen(SCALE);
 
   document.addEventListener("keydown", function (event) {
+    if (event.key == REWIND_KEY) {
+      rewinding = true;
+      return;
+    }
     chip8.keypress(event, true);
   });
 
   document.addEventListener("keyup", function (event) {
+    if (event.key == REWIND_KEY) {
+      rewinding = false;
+      return;
+    }
     chip8.keypress(event, false);
   });
 
+  quirks.addEventListener("change", function () {
+    xoChip.checked = quirks.value == "xochip";
+  });
+
+  mute.addEventListener("change", function () {
+    chip8.set_muted(mute.checked);
+  });
+
+  phosphor.addEventListener("change", function () {
+    chip8.set_phosphor(phosphor.checked);
+  });
+
+  decay.addEventListener("change", function () {
+    chip8.set_decay_frames(decay.valueAsNumber);
+  });
+
+  palette.addEventListener("change", function () {
+    chip8.set_palette(palette.value);
+    chip8.draw_screen(SCALE);
+  });
+
+  filter.addEventListener("change", function () {
+    chip8.set_filter(filter.value);
+    chip8.draw_screen(SCALE);
+  });
+
+  overlay.addEventListener("change", function () {
+    chip8.set_overlay(overlay.value);
+    chip8.draw_screen(SCALE);
+  });
+
+  screenshot.addEventListener("click", function () {
+    download(chip8.screenshot_png(CAPTURE_SCALE), "image/png", "chip8.png");
+  });
+
+  record.addEventListener("click", function () {
+    let gif = chip8.stop_recording();
+    if (gif) {
+      download(gif, "image/gif", "chip8.gif");
+      record.textContent = "Record";
+    } else {
+      chip8.start_recording(CAPTURE_SCALE);
+      record.textContent = "Stop recording";
+    }
+  });
+
   input.addEventListener(
     "change",
     function (event) {
+      chip8.resume_audio();
       if (anim_frame != 0) {
         window.cancelAnimationFrame(anim_frame);
       }
@@ -45,8 +114,17 @@ async function run() {
       fileReader.onload = function (e) {
         let buffer = fileReader.result;
         const rom = new Uint8Array(buffer);
+        chip8.set_quirks(quirks.value);
+        chip8.set_xo_chip(xoChip.checked);
         chip8.reset();
-        chip8.load(rom);
+        faulted = false;
+        status.textContent = "";
+        try {
+          chip8.load(rom);
+        } catch (err) {
+          status.textContent = `Unable to load ROM: ${err}`;
+          return;
+        }
         gameloap(chip8);
       };
       fileReader.readAsArrayBuffer(file);
@@ -56,14 +134,22 @@ async function run() {
 }
 
 function gameloap(chip8) {
-  for (let i = 0; i < TICKS_PER_FRAME; i++) {
-    chip8.tick();
+  if (rewinding) {
+    if (chip8.rewind()) {
+      faulted = false;
+      status.textContent = "";
+    }
+  } else if (!faulted) {
+    try {
+      chip8.run_frame().free();
+      chip8.play_audio();
+      chip8.record_frame();
+    } catch (err) {
+      faulted = true;
+      status.textContent = `Fault: ${err}`;
+    }
   }
-  chip8.tick_timers();
 
-  context.fillStyle = "black";
-  context.fillRect(0, 0, WIDTH * SCALE, HEIGHT * SCALE);
-  context.fillStyle = "white";
   chip8.draw_screen(SCALE);
 
   anim_frame = window.requestAnimationFrame(() => {
@@ -71,4 +157,13 @@ function gameloap(chip8) {
   });
 }
 
+function download(bytes, type, name) {
+  const url = URL.createObjectURL(new Blob([bytes], { type }));
+  const link = document.createElement("a");
+  link.href = url;
+  link.download = name;
+  link.click();
+  URL.revokeObjectURL(url);
+}
+
 run().catch(console.error);